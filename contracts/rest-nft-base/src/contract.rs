@@ -6,37 +6,93 @@ use cw2::{get_contract_version, set_contract_version};
 pub use cw721_base::{MintMsg, MinterResponse};
 use rest_nft::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 use rest_nft::state::RestNFTContract;
+use semver::Version;
 
 use crate::execute::{
-    execute_buy, execute_freeze, execute_mint, execute_set_available, execute_set_buy_amount,
-    execute_set_level, execute_set_minter, execute_update,
+    check_not_paused, check_transferable, execute_add_experience, execute_bid_buyout,
+    execute_breed, execute_buy, execute_claim_rental_payout, execute_detach, execute_equip,
+    execute_evolve, execute_fractionalize, execute_freeze, execute_fuse, execute_list_for_rent,
+    execute_mint, execute_patch_metadata, execute_receive, execute_receive_nft,
+    execute_recompute_rarity, execute_recover_token, execute_redeem, execute_reindex, execute_rent,
+    execute_set_available, execute_set_base_uri, execute_set_breeding, execute_set_buy_amount,
+    execute_set_child_contract, execute_set_collection_info, execute_set_evolution,
+    execute_set_exchange_source, execute_set_guardian, execute_set_history_retention,
+    execute_set_level, execute_set_minter, execute_set_paused, execute_set_paused_operations,
+    execute_set_progression, execute_set_recipe, execute_set_transfer_lock,
+    execute_set_transferable, execute_set_user, execute_settle_buyout, execute_stake,
+    execute_transfer, execute_transfer_shares, execute_unequip, execute_unlist, execute_unstake,
+    execute_update, validate_name, validate_sale, validate_symbol,
 };
 
-use crate::query::{query_config, query_frozen, query_sales};
-use crate::state::{Config, Sales, CONFIG, SALES};
+use crate::events::{config_event, migrate_event};
+use crate::migrate::{
+    parse_version, run_migrations, CONTRACT_NAME, CONTRACT_VERSION, LEGACY_CONTRACT_NAME,
+};
+use crate::query::{
+    query_all_nft_info, query_all_nft_info_batch, query_all_tokens, query_collection_info,
+    query_config, query_equipped, query_evolution, query_evolutions, query_exchange_sources,
+    query_exchanged, query_experience, query_frozen, query_lineage, query_metadata_history,
+    query_nft_info, query_nft_info_range, query_pause_status, query_rarity, query_rarity_ranking,
+    query_recipe, query_recipes, query_rental, query_rental_listings, query_sales,
+    query_sales_history, query_sales_stats, query_share_holders, query_shares, query_stake_info,
+    query_staked_tokens, query_tokens_by_trait, query_tokens_with_info, query_trait_counts,
+    query_transfer_lock, query_transferable, query_user_of, query_vault,
+};
+use crate::state::{
+    Config, MigrateConfig, Sales, SalesStats, COLLECTION_INFO, CONFIG, SALES, SALES_STATS,
+};
 use crate::{error::ContractError, execute::execute_burn};
 
+fn validate_instantiate(deps: Deps, msg: &InstantiateMsg) -> Result<(), ContractError> {
+    validate_name(&msg.name)?;
+    validate_symbol(&msg.symbol)?;
+
+    deps.api.addr_validate(&msg.minter)?;
+
+    if msg.token_supply == Some(0) {
+        return Err(ContractError::InvalidTokenSupply {});
+    }
+
+    validate_sale(msg.buy_amount, msg.available)
+}
+
 #[cfg_attr(not(feature = "library"), entry_point)]
 pub fn instantiate(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     msg: InstantiateMsg,
-) -> StdResult<Response> {
+) -> Result<Response, ContractError> {
+    validate_instantiate(deps.as_ref(), &msg)?;
+
     let config = Config {
         buy_amount: msg.buy_amount,
         token_supply: msg.token_supply,
-        available: true,
+        available: msg.available,
         frozen: false,
+        base_uri: None,
+        token_uri_suffix: None,
+        history_retention: None,
+        progression: None,
+        transferable: true,
+        transfer_lock: None,
+        breed_cooldown: None,
     };
 
-    let sales = Sales { count: 0 };
+    let sales = Sales { last_token_id: 0 };
 
     SALES.save(deps.storage, &sales)?;
+    SALES_STATS.save(deps.storage, &SalesStats::default())?;
 
     CONFIG.save(deps.storage, &config)?;
 
-    RestNFTContract::default().instantiate(deps, env, info, msg.into())
+    let collection_info = msg.collection_info.clone().unwrap_or_default();
+    COLLECTION_INFO.save(deps.storage, &collection_info)?;
+
+    let response = RestNFTContract::default().instantiate(deps.branch(), env, info, msg.into())?;
+    // overrides the version stored by cw721-base
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+    Ok(response)
 }
 
 #[cfg_attr(not(feature = "library"), entry_point)]
@@ -46,12 +102,20 @@ pub fn execute(
     info: MessageInfo,
     msg: ExecuteMsg,
 ) -> Result<Response, ContractError> {
+    check_not_paused(deps.storage, &msg)?;
+    check_transferable(deps.storage, &msg)?;
+
     match msg {
         ExecuteMsg::Update {
             token_id,
             token_uri,
             extension,
         } => execute_update(deps, env, info, token_id, token_uri, extension),
+        ExecuteMsg::PatchMetadata {
+            token_id,
+            patch,
+            expected_version,
+        } => execute_patch_metadata(deps, env, info, token_id, patch, expected_version),
         // Freeze token metadata
         ExecuteMsg::Freeze {} => execute_freeze(deps, env, info),
 
@@ -64,7 +128,25 @@ pub fn execute(
         // Set minter
         ExecuteMsg::SetMinter { minter } => execute_set_minter(deps, env, info, minter),
 
-        ExecuteMsg::SetLevel { token_id, level } => execute_set_level(deps, info, token_id, level),
+        ExecuteMsg::SetLevel { token_id, level } => {
+            execute_set_level(deps, env, info, token_id, level)
+        }
+
+        ExecuteMsg::SetProgression {
+            game_server,
+            level_thresholds,
+            max_level,
+        } => execute_set_progression(deps, info, game_server, level_thresholds, max_level),
+
+        ExecuteMsg::AddExperience { token_id, amount } => {
+            execute_add_experience(deps, env, info, token_id, amount)
+        }
+
+        ExecuteMsg::RecomputeRarity { start_after, limit } => {
+            execute_recompute_rarity(deps, info, start_after, limit)
+        }
+
+        ExecuteMsg::Reindex { limit } => execute_reindex(deps, env, info, limit),
 
         ExecuteMsg::SetBuyAmount { buy_amount } => execute_set_buy_amount(deps, info, buy_amount),
 
@@ -72,6 +154,153 @@ pub fn execute(
 
         ExecuteMsg::Buy { recipient } => execute_buy(deps, env, info, recipient),
 
+        ExecuteMsg::SetCollectionInfo {
+            name,
+            symbol,
+            collection_info,
+        } => execute_set_collection_info(deps, info, name, symbol, collection_info),
+
+        ExecuteMsg::SetBaseUri { base_uri, suffix } => {
+            execute_set_base_uri(deps, info, base_uri, suffix)
+        }
+
+        ExecuteMsg::SetHistoryRetention { retention } => {
+            execute_set_history_retention(deps, info, retention)
+        }
+
+        ExecuteMsg::SetGuardian { guardian } => execute_set_guardian(deps, info, guardian),
+
+        ExecuteMsg::Pause {} => execute_set_paused(deps, info, true),
+
+        ExecuteMsg::Unpause {} => execute_set_paused(deps, info, false),
+
+        ExecuteMsg::SetPausedOperations { operations } => {
+            execute_set_paused_operations(deps, info, operations)
+        }
+
+        ExecuteMsg::SetTransferable {
+            token_id,
+            transferable,
+        } => execute_set_transferable(deps, info, token_id, transferable),
+
+        ExecuteMsg::SetTransferLock { duration } => execute_set_transfer_lock(deps, info, duration),
+
+        ExecuteMsg::Fuse {
+            token_ids,
+            recipe_id,
+        } => execute_fuse(deps, env, info, token_ids, recipe_id),
+
+        ExecuteMsg::Breed { parent_a, parent_b } => {
+            execute_breed(deps, env, info, parent_a, parent_b)
+        }
+
+        ExecuteMsg::SetBreeding { cooldown } => execute_set_breeding(deps, info, cooldown),
+
+        ExecuteMsg::Fractionalize {
+            token_id,
+            shares,
+            reserve_price,
+            auction_duration,
+        } => execute_fractionalize(
+            deps,
+            env,
+            info,
+            token_id,
+            shares,
+            reserve_price,
+            auction_duration,
+        ),
+
+        ExecuteMsg::TransferShares {
+            token_id,
+            recipient,
+            amount,
+        } => execute_transfer_shares(deps, info, token_id, recipient, amount),
+
+        ExecuteMsg::BidBuyout { token_id } => execute_bid_buyout(deps, env, info, token_id),
+
+        ExecuteMsg::SettleBuyout { token_id } => execute_settle_buyout(deps, env, info, token_id),
+
+        ExecuteMsg::Redeem { token_id } => execute_redeem(deps, info, token_id),
+
+        ExecuteMsg::ListForRent {
+            token_id,
+            price_per_day,
+            max_days,
+        } => execute_list_for_rent(deps, env, info, token_id, price_per_day, max_days),
+
+        ExecuteMsg::Unlist { token_id } => execute_unlist(deps, env, info, token_id),
+
+        ExecuteMsg::Rent { token_id, days } => execute_rent(deps, env, info, token_id, days),
+
+        ExecuteMsg::ClaimRentalPayout { token_id } => {
+            execute_claim_rental_payout(deps, env, info, token_id)
+        }
+
+        ExecuteMsg::SetUser {
+            token_id,
+            user,
+            expires,
+        } => execute_set_user(deps, env, info, token_id, user, expires),
+
+        ExecuteMsg::Equip {
+            token_id,
+            child_token_id,
+        } => execute_equip(deps, env, info, token_id, child_token_id),
+
+        ExecuteMsg::Unequip { token_id, child } => {
+            execute_unequip(deps, env, info, token_id, child)
+        }
+
+        ExecuteMsg::Detach { token_id, child } => execute_detach(deps, env, info, token_id, child),
+
+        ExecuteMsg::ReceiveNft(msg) => execute_receive_nft(deps, env, info, msg),
+
+        ExecuteMsg::Receive(msg) => execute_receive(deps, env, info, msg),
+
+        ExecuteMsg::SetChildContract { contract } => {
+            execute_set_child_contract(deps, info, contract, true)
+        }
+
+        ExecuteMsg::RemoveChildContract { contract } => {
+            execute_set_child_contract(deps, info, contract, false)
+        }
+
+        ExecuteMsg::SetExchangeSource { contract, source } => {
+            execute_set_exchange_source(deps, info, contract, Some(source))
+        }
+
+        ExecuteMsg::RemoveExchangeSource { contract } => {
+            execute_set_exchange_source(deps, info, contract, None)
+        }
+
+        ExecuteMsg::Evolve { token_id } => execute_evolve(deps, env, info, token_id),
+
+        ExecuteMsg::SetEvolution { stage, evolution } => {
+            execute_set_evolution(deps, info, stage, Some(evolution))
+        }
+
+        ExecuteMsg::RemoveEvolution { stage } => execute_set_evolution(deps, info, stage, None),
+
+        ExecuteMsg::SetRecipe { recipe_id, recipe } => {
+            execute_set_recipe(deps, info, recipe_id, Some(recipe))
+        }
+
+        ExecuteMsg::RemoveRecipe { recipe_id } => execute_set_recipe(deps, info, recipe_id, None),
+
+        ExecuteMsg::Stake { token_id } => execute_stake(deps, env, info, token_id),
+
+        ExecuteMsg::Unstake { token_id } => execute_unstake(deps, env, info, token_id),
+
+        ExecuteMsg::RecoverToken {
+            token_id,
+            recipient,
+        } => execute_recover_token(deps, env, info, token_id, recipient),
+
+        ExecuteMsg::TransferNft { .. } | ExecuteMsg::SendNft { .. } => {
+            execute_transfer(deps, env, info, msg)
+        }
+
         // CW721 methods
         _ => RestNFTContract::default()
             .execute(deps, env, info, msg.into())
@@ -84,7 +313,126 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
     match msg {
         QueryMsg::Config {} => to_binary(&query_config(deps)?),
         QueryMsg::Sales {} => to_binary(&query_sales(deps)?),
+        QueryMsg::SalesHistory { start_after, limit } => {
+            to_binary(&query_sales_history(deps, start_after, limit)?)
+        }
+        QueryMsg::SalesStats {} => to_binary(&query_sales_stats(deps)?),
+        QueryMsg::PauseStatus {} => to_binary(&query_pause_status(deps)?),
+        QueryMsg::Lineage { token_id } => to_binary(&query_lineage(deps, env.clone(), token_id)?),
+        QueryMsg::Vault { token_id } => to_binary(&query_vault(deps, token_id)?),
+        QueryMsg::Shares { token_id, holder } => to_binary(&query_shares(deps, token_id, holder)?),
+        QueryMsg::ShareHolders {
+            token_id,
+            start_after,
+            limit,
+        } => to_binary(&query_share_holders(deps, token_id, start_after, limit)?),
+        QueryMsg::Rental { token_id } => to_binary(&query_rental(deps, token_id)?),
+        QueryMsg::RentalListings { start_after, limit } => {
+            to_binary(&query_rental_listings(deps, start_after, limit)?)
+        }
+        QueryMsg::UserOf { token_id } => to_binary(&query_user_of(deps, env, token_id)?),
+        QueryMsg::Exchanged {
+            source_contract,
+            source_token_id,
+        } => to_binary(&query_exchanged(deps, source_contract, source_token_id)?),
+        QueryMsg::ExchangeSources { start_after, limit } => {
+            to_binary(&query_exchange_sources(deps, start_after, limit)?)
+        }
+        QueryMsg::Equipped { token_id } => to_binary(&query_equipped(deps, env, token_id)?),
+        QueryMsg::Evolution { stage } => to_binary(&query_evolution(deps, stage)?),
+        QueryMsg::Evolutions { start_after, limit } => {
+            to_binary(&query_evolutions(deps, start_after, limit)?)
+        }
+        QueryMsg::Recipe { recipe_id } => to_binary(&query_recipe(deps, recipe_id)?),
+        QueryMsg::Recipes { start_after, limit } => {
+            to_binary(&query_recipes(deps, start_after, limit)?)
+        }
+        QueryMsg::StakeInfo { token_id } => {
+            to_binary(&query_stake_info(deps, env.clone(), token_id)?)
+        }
+        QueryMsg::StakedTokens {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&query_staked_tokens(deps, owner, start_after, limit)?),
+        QueryMsg::TransferLock { token_id } => {
+            to_binary(&query_transfer_lock(deps, env.clone(), token_id)?)
+        }
+        QueryMsg::Transferable { token_id } => to_binary(&query_transferable(deps, token_id)?),
         QueryMsg::Frozen {} => to_binary(&query_frozen(deps)?),
+        QueryMsg::CollectionInfo {} => to_binary(&query_collection_info(deps)?),
+        QueryMsg::Experience { token_id } => to_binary(&query_experience(deps, token_id)?),
+        QueryMsg::TokensByTrait {
+            trait_type,
+            value,
+            start_after,
+            limit,
+        } => to_binary(&query_tokens_by_trait(
+            deps,
+            trait_type,
+            value,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::TraitCounts {
+            trait_type,
+            start_after,
+            limit,
+        } => to_binary(&query_trait_counts(deps, trait_type, start_after, limit)?),
+        QueryMsg::Rarity { token_id } => to_binary(&query_rarity(deps, token_id)?),
+        QueryMsg::RarityRanking { start_after, limit } => {
+            to_binary(&query_rarity_ranking(deps, start_after, limit)?)
+        }
+        QueryMsg::TokensWithInfo {
+            owner,
+            start_after,
+            limit,
+        } => to_binary(&query_tokens_with_info(
+            deps,
+            env,
+            owner,
+            start_after,
+            limit,
+        )?),
+        QueryMsg::AllTokens {
+            start_after,
+            end_before,
+            limit,
+            reverse,
+        } => to_binary(&query_all_tokens(
+            deps,
+            start_after,
+            end_before,
+            limit,
+            reverse.unwrap_or(false),
+        )?),
+        QueryMsg::NftInfoRange { start_after, limit } => {
+            to_binary(&query_nft_info_range(deps, start_after, limit)?)
+        }
+        QueryMsg::AllNftInfoBatch {
+            token_ids,
+            include_expired,
+        } => to_binary(&query_all_nft_info_batch(
+            deps,
+            env,
+            token_ids,
+            include_expired.unwrap_or(false),
+        )?),
+        QueryMsg::MetadataHistory {
+            token_id,
+            start_after,
+            limit,
+        } => to_binary(&query_metadata_history(deps, token_id, start_after, limit)?),
+        QueryMsg::NftInfo { token_id } => to_binary(&query_nft_info(deps, token_id)?),
+        QueryMsg::AllNftInfo {
+            token_id,
+            include_expired,
+        } => to_binary(&query_all_nft_info(
+            deps,
+            env,
+            token_id,
+            include_expired.unwrap_or(false),
+        )?),
         // CW721 methods
         _ => RestNFTContract::default().query(deps, env, msg.into()),
     }
@@ -94,26 +442,58 @@ pub fn query(deps: Deps, env: Env, msg: QueryMsg) -> StdResult<Binary> {
 pub fn migrate(
     deps: DepsMut,
     _env: Env,
-    msg: MigrateMsg<Config>,
+    msg: MigrateMsg<MigrateConfig>,
 ) -> Result<Response, ContractError> {
-    match msg {
-        MigrateMsg { version, config } => try_migrate(deps, version, config),
-    }
+    let MigrateMsg { config } = msg;
+    try_migrate(deps, config)
 }
 
-fn try_migrate(
-    deps: DepsMut,
-    version: String,
-    config: Option<Config>,
-) -> Result<Response, ContractError> {
+fn try_migrate(deps: DepsMut, config: Option<MigrateConfig>) -> Result<Response, ContractError> {
     let contract_version = get_contract_version(deps.storage)?;
-    set_contract_version(deps.storage, contract_version.contract, version)?;
-
-    if config.is_some() {
-        CONFIG.save(deps.storage, &config.unwrap())?
+    if contract_version.contract != CONTRACT_NAME
+        && contract_version.contract != LEGACY_CONTRACT_NAME
+    {
+        return Err(ContractError::WrongContract {
+            expected: CONTRACT_NAME.to_string(),
+            actual: contract_version.contract,
+        });
     }
 
-    Ok(Response::new()
+    // the cw721-base migrate let admins store any version, so legacy
+    // deployments run every migration whatever version they report
+    let to = parse_version(CONTRACT_VERSION)?;
+    let from = if contract_version.contract == LEGACY_CONTRACT_NAME {
+        Version::new(0, 0, 0)
+    } else {
+        let from = parse_version(&contract_version.version)?;
+        if from > to {
+            return Err(ContractError::CannotDowngrade {
+                stored: contract_version.version,
+                new: CONTRACT_VERSION.to_string(),
+            });
+        }
+        from
+    };
+
+    let applied = run_migrations(deps.storage, &from, &to)?;
+    set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+
+    let mut response = Response::new()
         .add_attribute("method", "try_migrate")
-        .add_attribute("version", contract_version.version))
+        .add_attribute("version", contract_version.version.clone())
+        .add_attribute("migrations", applied.join(","))
+        .add_event(migrate_event(&contract_version.version, CONTRACT_VERSION));
+
+    if let Some(config) = config {
+        let old_config = CONFIG.load(deps.storage)?;
+        let new_config = config.into_config(old_config.frozen);
+        if new_config.token_supply == Some(0) {
+            return Err(ContractError::InvalidTokenSupply {});
+        }
+        validate_sale(new_config.buy_amount, new_config.available)?;
+        CONFIG.save(deps.storage, &new_config)?;
+        response = response.add_event(config_event("config", &old_config, &new_config)?);
+    }
+
+    Ok(response)
 }
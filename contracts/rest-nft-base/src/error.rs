@@ -1,4 +1,5 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{StdError, Timestamp};
+use cw0::Expiration;
 use cw721_base::ContractError as CW721ContractError;
 use thiserror::Error;
 
@@ -23,11 +24,155 @@ pub enum ContractError {
     #[error("Cannot update frozen contract")]
     ContractFrozen {},
 
+    #[error("Contract must be frozen")]
+    ContractNotFrozen {},
+
     #[error("Token supply exhausted")]
     MaxTokenSupply {},
 
     #[error("Not enougth funds")]
     Funds {},
+
+    #[error("Level thresholds must be ascending and max level at least 1")]
+    InvalidProgression {},
+
+    #[error("Level must be a number between 1 and {max_level}")]
+    InvalidLevel { max_level: u32 },
+
+    #[error("Progression not configured")]
+    ProgressionNotSet {},
+
+    #[error("Metadata version mismatch (expected {expected}, current {current})")]
+    VersionMismatch { expected: u64, current: u64 },
+
+    #[error("Token is locked until {expires}")]
+    TransferLocked { expires: Expiration },
+
+    #[error("No evolution from the token stage")]
+    EvolutionNotFound {},
+
+    #[error("Token must reach level {min_level} to evolve")]
+    LevelTooLow { min_level: u32 },
+
+    #[error("Token cannot evolve until {until}")]
+    HeldTooShort { until: Timestamp },
+
+    #[error("Token is rented until {expires}")]
+    TokenRented { expires: Expiration },
+
+    #[error("Token is not listed for rent")]
+    NotListed {},
+
+    #[error("Rental must last between 1 and {max_days} days")]
+    InvalidRentalDays { max_days: u32 },
+
+    #[error("Rental price must be a non zero amount of a denom")]
+    InvalidRentalPrice {},
+
+    #[error("No rental to claim")]
+    RentalNotFound {},
+
+    #[error("Token is not fractionalized")]
+    NotFractionalized {},
+
+    #[error("Token has a vault whose shares are not all redeemed")]
+    Fractionalized {},
+
+    #[error("Shares must be more than zero")]
+    InvalidShares {},
+
+    #[error("Reserve price must be a non zero amount of a denom")]
+    InvalidReservePrice {},
+
+    #[error("Auction must last between 1 and {max} seconds")]
+    InvalidAuctionDuration { max: u64 },
+
+    #[error("Bid must be at least {minimum}")]
+    BidTooLow { minimum: String },
+
+    #[error("Auction ended")]
+    AuctionEnded {},
+
+    #[error("Auction has not ended")]
+    AuctionNotEnded {},
+
+    #[error("Token is not bought out")]
+    NotBoughtOut {},
+
+    #[error("Expiration already passed")]
+    InvalidExpiration {},
+
+    #[error("Token cannot be equipped to itself")]
+    InvalidChild {},
+
+    #[error("Child is not equipped to the token")]
+    ChildNotFound {},
+
+    #[error("Assets of the sender cannot be equipped")]
+    NotChildContract {},
+
+    #[error("Token has equipped children")]
+    TokenHasChildren {},
+
+    #[error("Insufficient balance")]
+    InsufficientBalance {},
+
+    #[error("Tokens of the sender cannot be exchanged")]
+    NotExchangeSource {},
+
+    #[error("Breeding is disabled")]
+    BreedingDisabled {},
+
+    #[error("Parents must be two different tokens")]
+    InvalidParents {},
+
+    #[error("Token cannot breed until {expires}")]
+    BreedCooldown { expires: Expiration },
+
+    #[error("Recipe not found")]
+    RecipeNotFound {},
+
+    #[error("Recipe needs {expected} distinct input tokens")]
+    InvalidRecipeInputs { expected: u32 },
+
+    #[error("Token is staked")]
+    TokenStaked {},
+
+    #[error("Token is not staked")]
+    TokenNotStaked {},
+
+    #[error("Token is not transferable")]
+    NonTransferable {},
+
+    #[error("Operation is paused")]
+    Paused {},
+
+    #[error("Name must be between {min} and {max} characters")]
+    InvalidName { min: usize, max: usize },
+
+    #[error("Symbol must be between {min} and {max} letters or '-'")]
+    InvalidSymbol { min: usize, max: usize },
+
+    #[error("Token supply must be greater than zero")]
+    InvalidTokenSupply {},
+
+    #[error("Sale cannot be available with a zero buy amount")]
+    InvalidSale {},
+
+    #[error("No reindex in progress")]
+    NoReindex {},
+
+    #[error("Tokens cannot be minted or burned until the reindex is done")]
+    Reindexing {},
+
+    #[error("Cannot migrate from contract {actual}, expected {expected}")]
+    WrongContract { expected: String, actual: String },
+
+    #[error("Cannot migrate from version {stored} down to {new}")]
+    CannotDowngrade { stored: String, new: String },
+
+    #[error("Invalid contract version: {version}")]
+    InvalidVersion { version: String },
 }
 
 impl From<CW721ContractError> for ContractError {
@@ -0,0 +1,289 @@
+use cosmwasm_std::{to_vec, Addr, Coin, Event, StdError, StdResult, Uint128};
+use serde::Serialize;
+
+use crate::state::TokenUser;
+
+use rest_nft::state::{
+    BuyoutBid, Child, Evolution, ExchangeRecord, ExchangeSource, MetadataChange, Recipe, Rental,
+    RentalListing, SaleRecord, Vault,
+};
+
+/// Version of the `rest_nft.*` event schema, bumped on breaking changes.
+/// Every event carries it as its first attribute.
+pub const EVENT_SCHEMA_VERSION: &str = "1";
+
+// Events are emitted as `wasm-rest_nft.<kind>` by the chain
+fn rest_nft_event(kind: &str) -> Event {
+    Event::new(format!("rest_nft.{}", kind)).add_attribute("version", EVENT_SCHEMA_VERSION)
+}
+
+// Encodes an event value as JSON so unset and structured values are unambiguous
+pub fn json<T: Serialize>(value: &T) -> StdResult<String> {
+    String::from_utf8(to_vec(value)?).map_err(StdError::invalid_utf8)
+}
+
+pub fn mint_event(token_id: &str, owner: &Addr, minter: &Addr) -> Event {
+    rest_nft_event("mint")
+        .add_attribute("token_id", token_id)
+        .add_attribute("owner", owner)
+        .add_attribute("minter", minter)
+}
+
+pub fn buy_event(sale: &SaleRecord, seller: &Addr) -> Event {
+    rest_nft_event("buy")
+        .add_attribute("sale_id", sale.id.to_string())
+        .add_attribute("token_id", &sale.token_id)
+        .add_attribute("seller", seller)
+        .add_attribute("buyer", &sale.buyer)
+        .add_attribute("recipient", &sale.recipient)
+        .add_attribute("price", sale.price.to_string())
+}
+
+pub fn transfer_event(token_id: &str, old_owner: &Addr, new_owner: &Addr) -> Event {
+    rest_nft_event("transfer")
+        .add_attribute("token_id", token_id)
+        .add_attribute("old_owner", old_owner)
+        .add_attribute("new_owner", new_owner)
+}
+
+pub fn transferable_event(token_id: Option<&str>, old: bool, new: bool) -> Event {
+    rest_nft_event("transferable")
+        .add_attribute("token_id", token_id.unwrap_or_default())
+        .add_attribute("old_transferable", old.to_string())
+        .add_attribute("new_transferable", new.to_string())
+}
+
+pub fn stake_event(token_id: &str, owner: &Addr) -> Event {
+    rest_nft_event("stake")
+        .add_attribute("token_id", token_id)
+        .add_attribute("owner", owner)
+}
+
+pub fn unstake_event(token_id: &str, owner: &Addr, staked_seconds: u64) -> Event {
+    rest_nft_event("unstake")
+        .add_attribute("token_id", token_id)
+        .add_attribute("owner", owner)
+        .add_attribute("staked_seconds", staked_seconds.to_string())
+}
+
+pub fn fuse_event(token_id: &str, recipe_id: &str, inputs: &[String]) -> Event {
+    rest_nft_event("fuse")
+        .add_attribute("token_id", token_id)
+        .add_attribute("recipe_id", recipe_id)
+        .add_attribute("inputs", inputs.join(","))
+}
+
+pub fn breed_event(token_id: &str, parent_a: &str, parent_b: &str, generation: u32) -> Event {
+    rest_nft_event("breed")
+        .add_attribute("token_id", token_id)
+        .add_attribute("parent_a", parent_a)
+        .add_attribute("parent_b", parent_b)
+        .add_attribute("generation", generation.to_string())
+}
+
+pub fn fractionalize_event(token_id: &str, vault: &Vault) -> Event {
+    rest_nft_event("fractionalize")
+        .add_attribute("token_id", token_id)
+        .add_attribute("owner", &vault.owner)
+        .add_attribute("shares", vault.total_shares)
+        .add_attribute("reserve_price", vault.reserve_price.to_string())
+}
+
+pub fn shares_event(token_id: &str, from: &Addr, to: &Addr, amount: Uint128) -> Event {
+    rest_nft_event("shares")
+        .add_attribute("token_id", token_id)
+        .add_attribute("from", from)
+        .add_attribute("to", to)
+        .add_attribute("amount", amount)
+}
+
+pub fn bid_event(token_id: &str, bid: &BuyoutBid) -> Event {
+    rest_nft_event("bid")
+        .add_attribute("token_id", token_id)
+        .add_attribute("bidder", &bid.bidder)
+        .add_attribute("amount", bid.amount.to_string())
+}
+
+pub fn buyout_event(token_id: &str, bid: &BuyoutBid) -> Event {
+    rest_nft_event("buyout")
+        .add_attribute("token_id", token_id)
+        .add_attribute("buyer", &bid.bidder)
+        .add_attribute("price", bid.amount.to_string())
+}
+
+pub fn redeem_event(token_id: &str, holder: &Addr, shares: Uint128, payout: &Coin) -> Event {
+    rest_nft_event("redeem")
+        .add_attribute("token_id", token_id)
+        .add_attribute("holder", holder)
+        .add_attribute("shares", shares)
+        .add_attribute("payout", payout.to_string())
+}
+
+pub fn rental_listing_event(
+    token_id: &str,
+    old_listing: &Option<RentalListing>,
+    new_listing: &Option<RentalListing>,
+) -> StdResult<Event> {
+    Ok(rest_nft_event("rental_listing")
+        .add_attribute("token_id", token_id)
+        .add_attribute("old_listing", json(old_listing)?)
+        .add_attribute("new_listing", json(new_listing)?))
+}
+
+pub fn rent_event(token_id: &str, rental: &Rental) -> StdResult<Event> {
+    Ok(rest_nft_event("rent")
+        .add_attribute("token_id", token_id)
+        .add_attribute("renter", &rental.renter)
+        .add_attribute("owner", &rental.owner)
+        .add_attribute("payment", rental.payment.to_string())
+        .add_attribute("expires", json(&rental.expires)?))
+}
+
+pub fn rental_payout_event(token_id: &str, rental: &Rental) -> Event {
+    rest_nft_event("rental_payout")
+        .add_attribute("token_id", token_id)
+        .add_attribute("owner", &rental.owner)
+        .add_attribute("payment", rental.payment.to_string())
+}
+
+pub fn user_event(token_id: &str, user: Option<&TokenUser>) -> StdResult<Event> {
+    Ok(rest_nft_event("user")
+        .add_attribute("token_id", token_id)
+        .add_attribute("user", user.map(|u| u.user.to_string()).unwrap_or_default())
+        .add_attribute("expires", json(&user.map(|u| u.expires))?))
+}
+
+pub fn equip_event(token_id: &str, child: &Child) -> StdResult<Event> {
+    Ok(rest_nft_event("equip")
+        .add_attribute("token_id", token_id)
+        .add_attribute("child", json(child)?))
+}
+
+pub fn unequip_event(token_id: &str, child: &Child, recipient: &Addr) -> StdResult<Event> {
+    Ok(rest_nft_event("unequip")
+        .add_attribute("token_id", token_id)
+        .add_attribute("child", json(child)?)
+        .add_attribute("recipient", recipient))
+}
+
+pub fn detach_event(token_id: &str, child: &Child) -> StdResult<Event> {
+    Ok(rest_nft_event("detach")
+        .add_attribute("token_id", token_id)
+        .add_attribute("child", json(child)?))
+}
+
+pub fn child_contract_event(contract: &Addr, old_allowed: bool, new_allowed: bool) -> Event {
+    rest_nft_event("child_contract")
+        .add_attribute("contract", contract)
+        .add_attribute("old_allowed", old_allowed.to_string())
+        .add_attribute("new_allowed", new_allowed.to_string())
+}
+
+pub fn exchange_event(
+    source_contract: &Addr,
+    source_token_id: &str,
+    exchange: &ExchangeRecord,
+) -> Event {
+    rest_nft_event("exchange")
+        .add_attribute("source_contract", source_contract)
+        .add_attribute("source_token_id", source_token_id)
+        .add_attribute("token_id", &exchange.token_id)
+        .add_attribute("recipient", &exchange.recipient)
+}
+
+pub fn exchange_source_event(
+    contract: &Addr,
+    old_source: &Option<ExchangeSource>,
+    new_source: &Option<ExchangeSource>,
+) -> StdResult<Event> {
+    Ok(rest_nft_event("exchange_source")
+        .add_attribute("contract", contract)
+        .add_attribute("old_source", json(old_source)?)
+        .add_attribute("new_source", json(new_source)?))
+}
+
+pub fn evolve_event(token_id: &str, old_stage: &str, new_stage: Option<&str>) -> Event {
+    rest_nft_event("evolve")
+        .add_attribute("token_id", token_id)
+        .add_attribute("old_stage", old_stage)
+        .add_attribute("new_stage", new_stage.unwrap_or_default())
+}
+
+pub fn evolution_event(
+    stage: &str,
+    old_evolution: &Option<Evolution>,
+    new_evolution: &Option<Evolution>,
+) -> StdResult<Event> {
+    Ok(rest_nft_event("evolution")
+        .add_attribute("stage", stage)
+        .add_attribute("old_evolution", json(old_evolution)?)
+        .add_attribute("new_evolution", json(new_evolution)?))
+}
+
+pub fn recipe_event(
+    recipe_id: &str,
+    old_recipe: &Option<Recipe>,
+    new_recipe: &Option<Recipe>,
+) -> StdResult<Event> {
+    Ok(rest_nft_event("recipe")
+        .add_attribute("recipe_id", recipe_id)
+        .add_attribute("old_recipe", json(old_recipe)?)
+        .add_attribute("new_recipe", json(new_recipe)?))
+}
+
+pub fn burn_event(token_id: &str, owner: &Addr, sender: &Addr) -> Event {
+    rest_nft_event("burn")
+        .add_attribute("token_id", token_id)
+        .add_attribute("owner", owner)
+        .add_attribute("sender", sender)
+}
+
+pub fn update_event(token_id: &str, change: &MetadataChange) -> StdResult<Event> {
+    Ok(rest_nft_event("update")
+        .add_attribute("token_id", token_id)
+        .add_attribute("editor", &change.editor)
+        .add_attribute("metadata_version", change.version.to_string())
+        .add_attribute("old_token_uri", json(&change.old_token_uri)?)
+        .add_attribute("new_token_uri", json(&change.new_token_uri)?)
+        .add_attribute("old_extension", json(&change.old_extension)?)
+        .add_attribute("new_extension", json(&change.new_extension)?))
+}
+
+pub fn level_event(token_id: &str, old_level: Option<String>, new_level: &str) -> Event {
+    rest_nft_event("level")
+        .add_attribute("token_id", token_id)
+        .add_attribute("old_level", old_level.unwrap_or_default())
+        .add_attribute("new_level", new_level)
+}
+
+pub fn recompute_rarity_event(token_count: u64, last_token_id: &str) -> Event {
+    rest_nft_event("recompute_rarity")
+        .add_attribute("token_count", token_count.to_string())
+        .add_attribute("last_token_id", last_token_id)
+}
+
+pub fn reindex_event(last_token_id: &str, token_count: u64, done: bool) -> Event {
+    rest_nft_event("reindex")
+        .add_attribute("last_token_id", last_token_id)
+        .add_attribute("token_count", token_count.to_string())
+        .add_attribute("done", done.to_string())
+}
+
+pub fn config_event<T: Serialize>(key: &str, old_value: &T, new_value: &T) -> StdResult<Event> {
+    Ok(rest_nft_event("config")
+        .add_attribute("key", key)
+        .add_attribute("old_value", json(old_value)?)
+        .add_attribute("new_value", json(new_value)?))
+}
+
+pub fn minter_event(old_minter: &Addr, new_minter: &Addr) -> Event {
+    rest_nft_event("minter")
+        .add_attribute("old_minter", old_minter)
+        .add_attribute("new_minter", new_minter)
+}
+
+pub fn migrate_event(old_version: &str, new_version: &str) -> Event {
+    rest_nft_event("migrate")
+        .add_attribute("old_version", old_version)
+        .add_attribute("new_version", new_version)
+}
@@ -1,17 +1,180 @@
-use cosmwasm_std::{coins, BankMsg, Deps, DepsMut, Env, MessageInfo, Response, Uint128};
+use cosmwasm_std::{
+    coin, from_binary, to_binary, Addr, BankMsg, Coin, Deps, DepsMut, Empty, Env, Event,
+    MessageInfo, Order, Response, StdError, StdResult, Storage, Timestamp, Uint128, Uint64,
+    WasmMsg,
+};
+use cw0::{Duration, Expiration};
+use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+use cw721::{Cw721ExecuteMsg, Cw721ReceiveMsg};
+use cw_storage_plus::Bound;
+use sha2::{Digest, Sha256};
 use std::convert::From;
 
 use cw721_base::state::TokenInfo;
 use cw721_base::MintMsg;
 use rest_nft::{
-    msg::{ExecuteMsg},
-    state::{Trait, Extension, Metadata, RestNFTContract},
+    msg::{ExecuteMsg, RarityResponse, ReceiveMsg},
+    state::{
+        CollectionInfo, Extension, FieldPatch, MetadataChange, MetadataPatch, Operation,
+        RestNFTContract, SaleRecord, Trait, TraitPatch,
+    },
 };
 
-use crate::contract::execute;
-
 use crate::error::ContractError;
-use crate::state::{Config, Sales, CONFIG, SALES};
+use crate::events::{
+    bid_event, breed_event, burn_event, buy_event, buyout_event, child_contract_event,
+    config_event, detach_event, equip_event, evolution_event, evolve_event, exchange_event,
+    exchange_source_event, fractionalize_event, fuse_event, level_event, mint_event, minter_event,
+    recipe_event, recompute_rarity_event, redeem_event, reindex_event, rent_event,
+    rental_listing_event, rental_payout_event, shares_event, stake_event, transfer_event,
+    transferable_event, unequip_event, unstake_event, update_event, user_event,
+};
+use crate::query::{DEFAULT_LIMIT, MAX_LIMIT};
+use crate::state::{
+    equipped_nfts, stakes, token_traits, EquippedNft, Progression, Sales, Stake, TokenTrait,
+    TokenUser, BREED_COOLDOWNS, BREED_COUNT, BUYERS, CHILD_CONTRACTS, COLLECTION_INFO, CONFIG,
+    EQUIPPED_CW20, EVOLUTIONS, EXCHANGES, EXCHANGE_SOURCES, EXPERIENCE, FUSION_COUNT, HELD_SINCE,
+    LINEAGE, METADATA_HISTORY, METADATA_VERSION, PAUSE, RARITY_RANKING, RARITY_SCORES, RECIPES,
+    REINDEX, RENTALS, RENTAL_LISTINGS, SALES, SALES_HISTORY, SALES_STATS, SHARES, STAKED_SECONDS,
+    TRAIT_COUNTS, TRANSFERABLE, TRANSFER_LOCKS, USERS, VAULTS,
+};
+use rest_nft::state::{
+    BuyoutBid, Child, Evolution, ExchangeRecord, ExchangeSource, LevelInheritance, Lineage,
+    Metadata, Recipe, Rental, RentalListing, Vault,
+};
+
+// Operation class of a message, None for messages that can never be paused
+fn operation_of(msg: &ExecuteMsg) -> Option<Operation> {
+    match msg {
+        ExecuteMsg::TransferNft { .. }
+        | ExecuteMsg::SendNft { .. }
+        | ExecuteMsg::RecoverToken { .. }
+        | ExecuteMsg::Equip { .. }
+        | ExecuteMsg::Unequip { .. }
+        | ExecuteMsg::Detach { .. }
+        | ExecuteMsg::ReceiveNft(_)
+        | ExecuteMsg::Receive(_)
+        | ExecuteMsg::Fractionalize { .. }
+        | ExecuteMsg::TransferShares { .. }
+        | ExecuteMsg::SettleBuyout { .. } => Some(Operation::Transfer),
+        ExecuteMsg::Approve { .. }
+        | ExecuteMsg::Revoke { .. }
+        | ExecuteMsg::ApproveAll { .. }
+        | ExecuteMsg::RevokeAll { .. }
+        | ExecuteMsg::SetUser { .. }
+        | ExecuteMsg::ListForRent { .. }
+        | ExecuteMsg::Unlist { .. } => Some(Operation::Approve),
+        ExecuteMsg::Burn { .. } | ExecuteMsg::Fuse { .. } => Some(Operation::Burn),
+        ExecuteMsg::Buy { .. } | ExecuteMsg::Rent { .. } | ExecuteMsg::BidBuyout { .. } => {
+            Some(Operation::Buy)
+        }
+        ExecuteMsg::Mint(_) | ExecuteMsg::Breed { .. } => Some(Operation::Mint),
+        ExecuteMsg::Update { .. }
+        | ExecuteMsg::PatchMetadata { .. }
+        | ExecuteMsg::SetLevel { .. }
+        | ExecuteMsg::AddExperience { .. }
+        | ExecuteMsg::Evolve { .. } => Some(Operation::Update),
+        ExecuteMsg::Stake { .. } | ExecuteMsg::Unstake { .. } => Some(Operation::Stake),
+        _ => None,
+    }
+}
+
+// Rejects the message when its operation class is blocked by a pause
+pub fn check_not_paused(storage: &dyn Storage, msg: &ExecuteMsg) -> Result<(), ContractError> {
+    if let Some(operation) = operation_of(msg) {
+        let pause = PAUSE.may_load(storage)?.unwrap_or_default();
+        if pause.paused && pause.paused_operations.contains(&operation) {
+            return Err(ContractError::Paused {});
+        }
+    }
+    Ok(())
+}
+
+// Rejects transfers and approvals of non-transferable tokens. ApproveAll is
+// rejected when the collection itself is not transferable
+pub fn check_transferable(storage: &dyn Storage, msg: &ExecuteMsg) -> Result<(), ContractError> {
+    let token_id = match msg {
+        ExecuteMsg::TransferNft { token_id, .. }
+        | ExecuteMsg::SendNft { token_id, .. }
+        | ExecuteMsg::Approve { token_id, .. }
+        | ExecuteMsg::Equip {
+            child_token_id: token_id,
+            ..
+        }
+        | ExecuteMsg::Fractionalize { token_id, .. } => Some(token_id),
+        ExecuteMsg::ApproveAll { .. } => None,
+        _ => return Ok(()),
+    };
+
+    let config = CONFIG.load(storage)?;
+    let transferable = match token_id {
+        Some(token_id) => TRANSFERABLE
+            .may_load(storage, token_id)?
+            .unwrap_or(config.transferable),
+        None => config.transferable,
+    };
+    if !transferable {
+        return Err(ContractError::NonTransferable {});
+    }
+    Ok(())
+}
+
+// A sale can only be open at a non zero price
+pub fn validate_sale(buy_amount: u64, available: bool) -> Result<(), ContractError> {
+    if available && buy_amount == 0 {
+        return Err(ContractError::InvalidSale {});
+    }
+    Ok(())
+}
+
+// Timestamp::plus_seconds panics on overflow
+fn checked_plus_seconds(time: Timestamp, seconds: u64) -> StdResult<Timestamp> {
+    let nanos = Uint64::from(seconds)
+        .checked_mul(Uint64::from(1_000_000_000u64))?
+        .checked_add(Uint64::from(time.nanos()))?;
+    Ok(Timestamp::from_nanos(nanos.u64()))
+}
+
+pub const MIN_NAME_LENGTH: usize = 3;
+pub const MAX_NAME_LENGTH: usize = 50;
+pub const MIN_SYMBOL_LENGTH: usize = 3;
+pub const MAX_SYMBOL_LENGTH: usize = 12;
+
+pub fn validate_name(name: &str) -> Result<(), ContractError> {
+    let name_length = name.chars().count();
+    if !(MIN_NAME_LENGTH..=MAX_NAME_LENGTH).contains(&name_length) {
+        return Err(ContractError::InvalidName {
+            min: MIN_NAME_LENGTH,
+            max: MAX_NAME_LENGTH,
+        });
+    }
+    Ok(())
+}
+
+// Symbols are ascii letters, optionally separated by dashes
+pub fn validate_symbol(symbol: &str) -> Result<(), ContractError> {
+    let symbol_length = symbol.len();
+    if !(MIN_SYMBOL_LENGTH..=MAX_SYMBOL_LENGTH).contains(&symbol_length)
+        || !symbol
+            .bytes()
+            .all(|byte| byte.is_ascii_alphabetic() || byte == b'-')
+    {
+        return Err(ContractError::InvalidSymbol {
+            min: MIN_SYMBOL_LENGTH,
+            max: MAX_SYMBOL_LENGTH,
+        });
+    }
+    Ok(())
+}
+
+// The reindex counts tokens page by page, so the token count must not change
+// until it is done
+fn check_not_reindexing(storage: &dyn Storage) -> Result<(), ContractError> {
+    if REINDEX.may_load(storage)?.is_some() {
+        return Err(ContractError::Reindexing {});
+    }
+    Ok(())
+}
 
 pub fn execute_burn(
     deps: DepsMut,
@@ -19,22 +182,146 @@ pub fn execute_burn(
     info: MessageInfo,
     token_id: String,
 ) -> Result<Response, ContractError> {
+    let event = burn_token(deps, &env, &info, &token_id)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "burn")
+        .add_attribute("token_id", token_id)
+        .add_event(event))
+}
+
+// Burns a token the sender can send, returning its burn event
+fn burn_token(
+    deps: DepsMut,
+    env: &Env,
+    info: &MessageInfo,
+    token_id: &str,
+) -> Result<Event, ContractError> {
+    check_not_reindexing(deps.storage)?;
     let cw721_contract = RestNFTContract::default();
 
-    let token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    let token = cw721_contract.tokens.load(deps.storage, token_id)?;
     // validate send permissions
-    _check_can_send(&cw721_contract, deps.as_ref(), &env, &info, &token)?;
+    _check_can_send(&cw721_contract, deps.as_ref(), env, info, &token)?;
+
+    if stakes().may_load(deps.storage, token_id)?.is_some() {
+        return Err(ContractError::TokenStaked {});
+    }
+    if has_children(deps.storage, token_id)? {
+        return Err(ContractError::TokenHasChildren {});
+    }
+    check_not_rented(deps.storage, env, token_id)?;
 
-    cw721_contract.tokens.remove(deps.storage, &token_id)?;
+    cw721_contract.tokens.remove(deps.storage, token_id)?;
+    update_trait_index(deps.storage, token_id, &None)?;
+    TRANSFERABLE.remove(deps.storage, token_id);
+    TRANSFER_LOCKS.remove(deps.storage, token_id);
+    STAKED_SECONDS.remove(deps.storage, token_id);
+    BREED_COOLDOWNS.remove(deps.storage, token_id);
+    HELD_SINCE.remove(deps.storage, token_id);
+    USERS.remove(deps.storage, token_id);
+    RENTAL_LISTINGS.remove(deps.storage, token_id);
+    EXPERIENCE.remove(deps.storage, token_id);
+    if let Some(score) = RARITY_SCORES.may_load(deps.storage, token_id)? {
+        RARITY_SCORES.remove(deps.storage, token_id);
+        RARITY_RANKING.remove(deps.storage, (score.into(), token_id));
+    }
     cw721_contract
         .token_count
         .update(deps.storage, |count| -> Result<u64, ContractError> {
             Ok(count - 1)
         })?;
 
-    Ok(Response::new()
-        .add_attribute("action", "burn")
-        .add_attribute("token_id", token_id))
+    Ok(burn_event(token_id, &token.owner, &info.sender))
+}
+
+// Mints a token outside of the minter only Mint message, e.g. as the result
+// of a fusion, returning its mint event
+fn mint_token(
+    storage: &mut dyn Storage,
+    env: &Env,
+    token_id: &str,
+    owner: &Addr,
+    token_uri: Option<String>,
+    extension: Extension,
+) -> Result<Event, ContractError> {
+    check_not_reindexing(storage)?;
+    let cw721_contract = RestNFTContract::default();
+    let config = CONFIG.load(storage)?;
+    let count = cw721_contract.token_count(storage)?;
+    if let Some(token_supply) = config.token_supply {
+        if count >= token_supply {
+            return Err(ContractError::MaxTokenSupply {});
+        }
+    }
+
+    let token = TokenInfo {
+        owner: owner.clone(),
+        approvals: vec![],
+        token_uri,
+        extension,
+    };
+    cw721_contract
+        .tokens
+        .update(storage, token_id, |old| match old {
+            Some(_) => Err(ContractError::Claimed {}),
+            None => Ok(token.clone()),
+        })?;
+    cw721_contract.token_count.save(storage, &(count + 1))?;
+    update_trait_index(storage, token_id, &token.extension)?;
+    record_owner_change(storage, token_id, env.block.time)?;
+
+    let minter = cw721_contract.minter.load(storage)?;
+    Ok(mint_event(token_id, owner, &minter))
+}
+
+// Transfers or sends a token through cw721, recording the ownership change
+pub fn execute_transfer(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: ExecuteMsg,
+) -> Result<Response, ContractError> {
+    let token_id = match &msg {
+        ExecuteMsg::TransferNft { token_id, .. } | ExecuteMsg::SendNft { token_id, .. } => {
+            token_id.clone()
+        }
+        _ => return Err(StdError::generic_err("not a transfer message").into()),
+    };
+
+    if stakes().may_load(deps.storage, &token_id)?.is_some() {
+        return Err(ContractError::TokenStaked {});
+    }
+    check_not_rented(deps.storage, &env, &token_id)?;
+
+    if let Some(expires) = TRANSFER_LOCKS.may_load(deps.storage, &token_id)? {
+        if !expires.is_expired(&env.block) {
+            return Err(ContractError::TransferLocked { expires });
+        }
+        TRANSFER_LOCKS.remove(deps.storage, &token_id);
+    }
+
+    let cw721_contract = RestNFTContract::default();
+    let old_owner = cw721_contract.tokens.load(deps.storage, &token_id)?.owner;
+    let now = env.block.time;
+    let response = cw721_contract.execute(deps.branch(), env, info, msg.into())?;
+    let new_owner = cw721_contract.tokens.load(deps.storage, &token_id)?.owner;
+    record_owner_change(deps.storage, &token_id, now)?;
+
+    Ok(response.add_event(transfer_event(&token_id, &old_owner, &new_owner)))
+}
+
+// Called on every ownership change. Restarts the hold time and clears the
+// delegated user and rental listing, which never survive their owner
+fn record_owner_change(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    time: Timestamp,
+) -> StdResult<()> {
+    HELD_SINCE.save(storage, token_id, &time)?;
+    USERS.remove(storage, token_id);
+    RENTAL_LISTINGS.remove(storage, token_id);
+    Ok(())
 }
 
 // Copied private cw721 check here
@@ -75,9 +362,109 @@ fn _check_can_send<T>(
     }
 }
 
+// Distinct (trait_type, value) attributes of the token metadata
+fn token_traits_of(extension: &Extension) -> Vec<(String, String)> {
+    let mut traits: Vec<(String, String)> = extension
+        .iter()
+        .flat_map(|metadata| metadata.attributes.iter().flatten())
+        .map(|t| (t.trait_type.clone(), t.value.clone()))
+        .collect();
+    traits.sort();
+    traits.dedup();
+    traits
+}
+
+// Keeps the trait index and trait counts in sync with a token's metadata.
+// Diffs against the indexed traits, so tokens never indexed are handled too
+fn update_trait_index(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    extension: &Extension,
+) -> Result<(), ContractError> {
+    let old_traits: StdResult<Vec<_>> = token_traits()
+        .sub_prefix(token_id)
+        .range(storage, None, None, Order::Ascending)
+        .map(|item| item.map(|(_, t)| (t.trait_type, t.value)))
+        .collect();
+    let old_traits = old_traits?;
+    let new_traits = token_traits_of(extension);
+
+    for (trait_type, value) in old_traits.iter().filter(|t| !new_traits.contains(t)) {
+        token_traits().remove(storage, (token_id, trait_type, value))?;
+        let count = TRAIT_COUNTS
+            .may_load(storage, (trait_type, value))?
+            .unwrap_or_default();
+        if count <= 1 {
+            TRAIT_COUNTS.remove(storage, (trait_type, value));
+        } else {
+            TRAIT_COUNTS.save(storage, (trait_type, value), &(count - 1))?;
+        }
+    }
+
+    for (trait_type, value) in new_traits.iter().filter(|t| !old_traits.contains(t)) {
+        let token_trait = TokenTrait {
+            token_id: token_id.to_string(),
+            trait_type: trait_type.clone(),
+            value: value.clone(),
+        };
+        token_traits().save(storage, (token_id, trait_type, value), &token_trait)?;
+        TRAIT_COUNTS.update(storage, (trait_type, value), |count| -> StdResult<u64> {
+            Ok(count.unwrap_or_default() + 1)
+        })?;
+    }
+
+    Ok(())
+}
+
+// Appends a metadata change to the token history, pruning entries beyond
+// the configured retention, and returns the recorded change.
+// Called on every metadata change, also keeps the trait index in sync.
+fn record_metadata_change(
+    storage: &mut dyn Storage,
+    env: &Env,
+    editor: &Addr,
+    token_id: &str,
+    old_token: &TokenInfo<Extension>,
+    new_token: &TokenInfo<Extension>,
+) -> Result<MetadataChange, ContractError> {
+    let version = METADATA_VERSION
+        .may_load(storage, token_id)?
+        .unwrap_or_default()
+        + 1;
+    METADATA_VERSION.save(storage, token_id, &version)?;
+    update_trait_index(storage, token_id, &new_token.extension)?;
+
+    let change = MetadataChange {
+        version,
+        height: env.block.height,
+        editor: editor.clone(),
+        old_token_uri: old_token.token_uri.clone(),
+        new_token_uri: new_token.token_uri.clone(),
+        old_extension: old_token.extension.clone(),
+        new_extension: new_token.extension.clone(),
+    };
+    METADATA_HISTORY.save(storage, (token_id, version.into()), &change)?;
+
+    let config = CONFIG.load(storage)?;
+    if let Some(retention) = config.history_retention {
+        if version > retention {
+            let end = Bound::inclusive_int(version - retention);
+            let expired: Vec<Vec<u8>> = METADATA_HISTORY
+                .prefix(token_id)
+                .keys(storage, None, Some(end), Order::Ascending)
+                .collect();
+            for key in expired {
+                METADATA_HISTORY.remove(storage, (token_id, key.into()));
+            }
+        }
+    }
+
+    Ok(change)
+}
+
 pub fn execute_update(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
     token_id: String,
     token_uri: Option<String>,
@@ -95,7 +482,8 @@ pub fn execute_update(
         return Err(ContractError::ContractFrozen {});
     }
 
-    cw721_contract
+    let old_token = cw721_contract.tokens.may_load(deps.storage, &token_id)?;
+    let new_token = cw721_contract
         .tokens
         .update(deps.storage, &token_id, |token| match token {
             Some(mut token_info) => {
@@ -103,18 +491,32 @@ pub fn execute_update(
                 token_info.extension = extension;
                 Ok(token_info)
             }
-            None => return Err(ContractError::TokenNotFound {}),
+            None => Err(ContractError::TokenNotFound {}),
         })?;
 
+    let change = record_metadata_change(
+        deps.storage,
+        &env,
+        &info.sender,
+        &token_id,
+        &old_token.unwrap(),
+        &new_token,
+    )?;
+
     Ok(Response::new()
         .add_attribute("action", "update")
-        .add_attribute("token_id", token_id))
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("version", change.version.to_string())
+        .add_event(update_event(&token_id, &change)?))
 }
 
-pub fn execute_freeze(
+pub fn execute_patch_metadata(
     deps: DepsMut,
-    _env: Env,
+    env: Env,
     info: MessageInfo,
+    token_id: String,
+    patch: MetadataPatch,
+    expected_version: Option<u64>,
 ) -> Result<Response, ContractError> {
     let cw721_contract = RestNFTContract::default();
     let minter = cw721_contract.minter.load(deps.storage)?;
@@ -122,19 +524,139 @@ pub fn execute_freeze(
         return Err(ContractError::Unauthorized {});
     }
 
-    CONFIG.update(
+    let config = CONFIG.load(deps.storage)?;
+
+    if config.frozen {
+        return Err(ContractError::ContractFrozen {});
+    }
+
+    if let Some(expected) = expected_version {
+        let current = METADATA_VERSION
+            .may_load(deps.storage, &token_id)?
+            .unwrap_or_default();
+        if expected != current {
+            return Err(ContractError::VersionMismatch { expected, current });
+        }
+    }
+
+    let old_token = cw721_contract
+        .tokens
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::TokenNotFound {})?;
+
+    let mut new_token = old_token.clone();
+    let had_extension = new_token.extension.is_some();
+    let mut metadata = new_token.extension.unwrap_or_default();
+    let MetadataPatch {
+        token_uri,
+        image,
+        image_data,
+        external_url,
+        description,
+        name,
+        attributes,
+        background_color,
+        animation_url,
+        youtube_url,
+    } = patch;
+    for (field_patch, field) in [
+        (token_uri, &mut new_token.token_uri),
+        (image, &mut metadata.image),
+        (image_data, &mut metadata.image_data),
+        (external_url, &mut metadata.external_url),
+        (description, &mut metadata.description),
+        (name, &mut metadata.name),
+        (background_color, &mut metadata.background_color),
+        (animation_url, &mut metadata.animation_url),
+        (youtube_url, &mut metadata.youtube_url),
+    ] {
+        if let Some(field_patch) = field_patch {
+            field_patch.apply(field);
+        }
+    }
+
+    if let Some(trait_patches) = attributes {
+        let had_attributes = metadata.attributes.is_some();
+        let mut traits = metadata.attributes.take().unwrap_or_default();
+        for TraitPatch {
+            trait_type,
+            value,
+            display_type,
+        } in trait_patches
+        {
+            match value {
+                FieldPatch::Set(value) => {
+                    match traits.iter_mut().find(|t| t.trait_type == trait_type) {
+                        Some(existing) => {
+                            existing.value = value;
+                            if display_type.is_some() {
+                                existing.display_type = display_type;
+                            }
+                        }
+                        None => traits.push(Trait {
+                            display_type,
+                            trait_type,
+                            value,
+                        }),
+                    }
+                }
+                FieldPatch::Clear => traits.retain(|t| t.trait_type != trait_type),
+            }
+        }
+        if had_attributes || !traits.is_empty() {
+            metadata.attributes = Some(traits);
+        }
+    }
+
+    // A token without metadata only gets it when the patch sets a field
+    new_token.extension = if had_extension || metadata != Metadata::default() {
+        Some(metadata)
+    } else {
+        None
+    };
+    cw721_contract
+        .tokens
+        .save(deps.storage, &token_id, &new_token)?;
+
+    let change = record_metadata_change(
         deps.storage,
-        |mut config| -> Result<Config, ContractError> {
-            config.frozen = true;
-            Ok(config)
-        },
+        &env,
+        &info.sender,
+        &token_id,
+        &old_token,
+        &new_token,
     )?;
 
-    Ok(Response::new().add_attribute("action", "freeze"))
+    Ok(Response::new()
+        .add_attribute("action", "patch_metadata")
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("version", change.version.to_string())
+        .add_event(update_event(&token_id, &change)?))
+}
+
+pub fn execute_freeze(
+    deps: DepsMut,
+    _env: Env,
+    info: MessageInfo,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let old_frozen = config.frozen;
+    config.frozen = true;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "freeze")
+        .add_event(config_event("frozen", &old_frozen, &config.frozen)?))
 }
 
 pub fn execute_mint(
-    deps: DepsMut,
+    mut deps: DepsMut,
     env: Env,
     info: MessageInfo,
     mint_msg: MintMsg<Extension>,
@@ -147,17 +669,77 @@ pub fn execute_mint(
     if info.sender != minter {
         return Err(ContractError::Unauthorized {});
     }
+    check_not_reindexing(deps.storage)?;
 
     if config.token_supply.is_some() && current_count >= config.token_supply.unwrap() {
         return Err(ContractError::MaxTokenSupply {});
     }
 
-    let response = cw721_contract.mint(deps, env, info, mint_msg)?;
-    Ok(response)
+    let token_id = mint_msg.token_id.clone();
+    let extension = mint_msg.extension.clone();
+    let now = env.block.time;
+    let response = cw721_contract.mint(deps.branch(), env, info, mint_msg)?;
+    update_trait_index(deps.storage, &token_id, &extension)?;
+    record_owner_change(deps.storage, &token_id, now)?;
+    if let Some(level) = level_trait_of(&extension) {
+        save_level_experience(deps.storage, &token_id, &level, &config.progression)?;
+    }
+
+    let token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    Ok(response.add_event(mint_event(&token_id, &token.owner, &minter)))
+}
+
+pub const LEVEL_TRAIT: &str = "level";
+
+// Sets the level trait of the token metadata, adding it when missing
+fn set_level_trait(extension: Extension, level: String) -> Extension {
+    let mut metadata = extension.unwrap_or_default();
+    let mut attributes = metadata.attributes.unwrap_or_default();
+    let level_trait = Trait {
+        value: level,
+        trait_type: LEVEL_TRAIT.to_string(),
+        display_type: Some("null".to_string()),
+    };
+    match attributes.iter_mut().find(|t| t.trait_type == LEVEL_TRAIT) {
+        Some(existing) => *existing = level_trait,
+        None => attributes.push(level_trait),
+    }
+    metadata.attributes = Some(attributes);
+    Some(metadata)
+}
+
+// Current value of the level trait of the token metadata
+fn level_trait_of(extension: &Extension) -> Option<String> {
+    extension
+        .as_ref()
+        .and_then(|metadata| metadata.attributes.as_ref())
+        .and_then(|attributes| attributes.iter().find(|t| t.trait_type == LEVEL_TRAIT))
+        .map(|t| t.value.clone())
+}
+
+// Saves the experience needed for a level set directly, so the next
+// AddExperience does not recompute a lower level
+fn save_level_experience(
+    storage: &mut dyn Storage,
+    token_id: &str,
+    level: &str,
+    progression: &Option<Progression>,
+) -> StdResult<()> {
+    if let (Some(progression), Ok(level)) = (progression, level.parse::<u32>()) {
+        let experience = progression
+            .level_thresholds
+            .get(level.saturating_sub(1) as usize)
+            .or_else(|| progression.level_thresholds.last())
+            .copied()
+            .unwrap_or_default();
+        EXPERIENCE.save(storage, token_id, &experience)?;
+    }
+    Ok(())
 }
 
 pub fn execute_set_level(
     deps: DepsMut,
+    env: Env,
     info: MessageInfo,
     token_id: String,
     level: String,
@@ -169,49 +751,47 @@ pub fn execute_set_level(
         return Err(ContractError::Unauthorized {});
     }
 
-    cw721_contract
+    let config = CONFIG.load(deps.storage)?;
+    if let Some(progression) = &config.progression {
+        match level.parse::<u32>() {
+            Ok(value) if value >= 1 && value <= progression.max_level => {}
+            _ => {
+                return Err(ContractError::InvalidLevel {
+                    max_level: progression.max_level,
+                })
+            }
+        }
+    }
+
+    let old_token = cw721_contract.tokens.may_load(deps.storage, &token_id)?;
+    let new_token = cw721_contract
         .tokens
         .update(deps.storage, &token_id, |token| match token {
-            Some(token_info) => {
-                let mut update_token = token_info;
-                let metadata = update_token.extension.clone().unwrap();
-                let mut new_attributes = metadata.attributes.clone().unwrap();
-                let mut count = 0u32;
-                loop {
-                    let mut new_attribute = new_attributes[count as usize].clone();
-                    if new_attribute.trait_type == "level" {
-                        new_attribute = Trait {
-                            value: level,
-                            trait_type: "level".to_string(),
-                            display_type: Some("null".to_string())
-                        };
-                        new_attributes[count as usize] = new_attribute;
-                        break;
-                    }
-                    count += 1;
-
-                }
-                let new_metadata = Metadata {
-                    image: metadata.image,
-                    image_data: metadata.image_data,
-                    external_url: metadata.external_url,
-                    description: metadata.description,
-                    name: metadata.name,
-                    attributes: Some(new_attributes),
-                    background_color: metadata.background_color,
-                    animation_url: metadata.animation_url,
-                    youtube_url: metadata.youtube_url,
-                };
-                update_token.extension = Extension::Some(new_metadata);
-                Ok(update_token)
+            Some(mut token_info) => {
+                token_info.extension = set_level_trait(token_info.extension, level.clone());
+                Ok(token_info)
             }
-            None => return Err(ContractError::TokenNotFound {}),
+            None => Err(ContractError::TokenNotFound {}),
         })?;
 
+    let change = record_metadata_change(
+        deps.storage,
+        &env,
+        &info.sender,
+        &token_id,
+        old_token.as_ref().unwrap(),
+        &new_token,
+    )?;
+    save_level_experience(deps.storage, &token_id, &level, &config.progression)?;
+    let old_level = level_trait_of(&old_token.unwrap().extension);
+
     Ok(Response::new()
         .add_attribute("action", "set_level")
         .add_attribute("sender", info.sender)
-        .add_attribute("token_id", token_id))
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("version", change.version.to_string())
+        .add_event(update_event(&token_id, &change)?)
+        .add_event(level_event(&token_id, old_level, &level)))
 }
 
 pub fn execute_set_buy_amount(
@@ -226,18 +806,17 @@ pub fn execute_set_buy_amount(
         return Err(ContractError::Unauthorized {});
     }
 
-    CONFIG.update(
-        deps.storage,
-        |mut config| -> Result<Config, ContractError> {
-            config.buy_amount = buy_amount;
-            Ok(config)
-        },
-    )?;
+    let mut config = CONFIG.load(deps.storage)?;
+    validate_sale(buy_amount, config.available)?;
+    let old_buy_amount = config.buy_amount;
+    config.buy_amount = buy_amount;
+    CONFIG.save(deps.storage, &config)?;
 
     Ok(Response::new()
-        .add_attribute("action", "set_mint_amount")
+        .add_attribute("action", "set_buy_amount")
         .add_attribute("sender", info.sender)
-        .add_attribute("amount", buy_amount.to_string()))
+        .add_attribute("amount", buy_amount.to_string())
+        .add_event(config_event("buy_amount", &old_buy_amount, &buy_amount)?))
 }
 
 pub fn execute_set_available(
@@ -252,18 +831,17 @@ pub fn execute_set_available(
         return Err(ContractError::Unauthorized {});
     }
 
-    CONFIG.update(
-        deps.storage,
-        |mut config| -> Result<Config, ContractError> {
-            config.available = available;
-            Ok(config)
-        },
-    )?;
+    let mut config = CONFIG.load(deps.storage)?;
+    validate_sale(config.buy_amount, available)?;
+    let old_available = config.available;
+    config.available = available;
+    CONFIG.save(deps.storage, &config)?;
 
     Ok(Response::new()
         .add_attribute("action", "set_available")
         .add_attribute("sender", info.sender)
-        .add_attribute("available", available.to_string()))
+        .add_attribute("available", available.to_string())
+        .add_event(config_event("available", &old_available, &available)?))
 }
 
 pub fn execute_buy(
@@ -275,12 +853,12 @@ pub fn execute_buy(
     let cw721_contract = RestNFTContract::default();
     let minter = cw721_contract.minter.load(deps.storage)?;
     let sales = SALES.load(deps.storage)?;
-    let mut token_id = sales.count + 1;
+    let mut token_id = sales.last_token_id + 1;
     let config = CONFIG.load(deps.storage)?;
 
     let buy_amount = config.buy_amount;
 
-    if config.available != true {
+    if !config.available {
         return Err(ContractError::Unauthorized {});
     }
 
@@ -292,54 +870,106 @@ pub fn execute_buy(
         return Err(ContractError::Funds {});
     }
 
+    let price = coin(buy_amount.into(), "uusd");
     let message = BankMsg::Send {
         to_address: minter.to_string(),
-        amount: coins(buy_amount.into(), "uusd"),
+        amount: vec![price.clone()],
     };
 
     let mut token = cw721_contract
-    .tokens
-    .load(deps.storage, &token_id.to_string())?;
+        .tokens
+        .load(deps.storage, &token_id.to_string())?;
 
+    let recipient_addr = deps.api.addr_validate(&recipient)?;
     loop {
-        if token.owner == minter {
-            token.owner = deps.api.addr_validate(&recipient)?;
+        if token.owner == minter
+            && stakes()
+                .may_load(deps.storage, &token_id.to_string())?
+                .is_none()
+            && check_not_rented(deps.storage, &env, &token_id.to_string()).is_ok()
+        {
+            token.owner = recipient_addr.clone();
+            // approvals granted by the minter must not survive the sale
+            token.approvals = vec![];
             break;
         }
-        token_id = token_id + 1;
+        token_id += 1;
         token = cw721_contract
-        .tokens
-        .load(deps.storage, &token_id.to_string())?;
+            .tokens
+            .load(deps.storage, &token_id.to_string())?;
     }
 
-
     cw721_contract
         .tokens
         .save(deps.storage, &token_id.to_string(), &token)?;
-
-    cw721_contract
-        .token_count
-        .update(deps.storage, |count| -> Result<u64, ContractError> {
-            Ok(count + 1)
-        })?;
+    record_owner_change(deps.storage, &token_id.to_string(), env.block.time)?;
 
     SALES.update(deps.storage, |mut sales| -> Result<Sales, ContractError> {
-        sales.count = token_id;
+        sales.last_token_id = token_id;
         Ok(sales)
     })?;
 
-    let exec_msg = ExecuteMsg::TransferNft {
-        recipient: recipient.to_string(),
-        token_id: token_id.to_string(),
-    };
-    execute(deps, env, info.clone(), exec_msg).unwrap();
+    if let Some(duration) = config.transfer_lock {
+        TRANSFER_LOCKS.save(
+            deps.storage,
+            &token_id.to_string(),
+            &duration.after(&env.block),
+        )?;
+    }
+
+    let sale = record_sale(
+        deps.storage,
+        &env,
+        token_id.to_string(),
+        info.sender.clone(),
+        recipient_addr.clone(),
+        price,
+    )?;
 
     Ok(Response::new()
         .add_message(message)
         .add_attribute("action", "buy")
         .add_attribute("sender", info.sender)
         .add_attribute("recipient", recipient)
-        .add_attribute("token_id", token_id.to_string()))
+        .add_attribute("token_id", token_id.to_string())
+        .add_event(buy_event(&sale, &minter))
+        .add_event(transfer_event(&sale.token_id, &minter, &recipient_addr)))
+}
+
+// Appends a sale to the ledger and updates the aggregate sales stats
+fn record_sale(
+    storage: &mut dyn Storage,
+    env: &Env,
+    token_id: String,
+    buyer: Addr,
+    recipient: Addr,
+    price: Coin,
+) -> Result<SaleRecord, ContractError> {
+    let mut stats = SALES_STATS.may_load(storage)?.unwrap_or_default();
+    stats.sold += 1;
+
+    let purchases = BUYERS.may_load(storage, &buyer)?.unwrap_or_default();
+    if purchases == 0 {
+        stats.unique_buyers += 1;
+    }
+    BUYERS.save(storage, &buyer, &(purchases + 1))?;
+
+    match stats.revenue.iter_mut().find(|c| c.denom == price.denom) {
+        Some(revenue) => revenue.amount += price.amount,
+        None => stats.revenue.push(price.clone()),
+    }
+    SALES_STATS.save(storage, &stats)?;
+
+    let sale = SaleRecord {
+        id: stats.sold,
+        token_id,
+        buyer,
+        recipient,
+        price,
+        time: env.block.time,
+    };
+    SALES_HISTORY.save(storage, stats.sold.into(), &sale)?;
+    Ok(sale)
 }
 
 pub fn execute_set_minter(
@@ -357,5 +987,1977 @@ pub fn execute_set_minter(
     let new_minter = deps.api.addr_validate(&new_minter)?;
     cw721_contract.minter.save(deps.storage, &new_minter)?;
 
-    Ok(Response::new().add_attribute("action", "set_minter"))
+    Ok(Response::new()
+        .add_attribute("action", "set_minter")
+        .add_event(minter_event(&minter, &new_minter)))
+}
+
+pub fn execute_set_collection_info(
+    deps: DepsMut,
+    info: MessageInfo,
+    name: Option<String>,
+    symbol: Option<String>,
+    collection_info: CollectionInfo,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+
+    if config.frozen {
+        return Err(ContractError::ContractFrozen {});
+    }
+
+    let mut response = Response::new()
+        .add_attribute("action", "set_collection_info")
+        .add_attribute("sender", info.sender);
+
+    if name.is_some() || symbol.is_some() {
+        let mut contract_info = cw721_contract.contract_info.load(deps.storage)?;
+        if let Some(name) = name {
+            validate_name(&name)?;
+            response = response.add_event(config_event("name", &contract_info.name, &name)?);
+            contract_info.name = name;
+        }
+        if let Some(symbol) = symbol {
+            validate_symbol(&symbol)?;
+            response = response.add_event(config_event("symbol", &contract_info.symbol, &symbol)?);
+            contract_info.symbol = symbol;
+        }
+        cw721_contract
+            .contract_info
+            .save(deps.storage, &contract_info)?;
+    }
+
+    let old_collection_info = COLLECTION_INFO.may_load(deps.storage)?.unwrap_or_default();
+    COLLECTION_INFO.save(deps.storage, &collection_info)?;
+
+    Ok(response.add_event(config_event(
+        "collection_info",
+        &old_collection_info,
+        &collection_info,
+    )?))
+}
+
+pub fn execute_set_base_uri(
+    deps: DepsMut,
+    info: MessageInfo,
+    base_uri: Option<String>,
+    suffix: Option<String>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut config = CONFIG.load(deps.storage)?;
+    if config.frozen {
+        return Err(ContractError::ContractFrozen {});
+    }
+    let old_base_uri = std::mem::replace(&mut config.base_uri, base_uri.clone());
+    let old_suffix = std::mem::replace(&mut config.token_uri_suffix, suffix.clone());
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_base_uri")
+        .add_attribute("sender", info.sender)
+        .add_attribute("base_uri", base_uri.clone().unwrap_or_default())
+        .add_event(config_event("base_uri", &old_base_uri, &base_uri)?)
+        .add_event(config_event("token_uri_suffix", &old_suffix, &suffix)?))
+}
+
+pub fn execute_set_history_retention(
+    deps: DepsMut,
+    info: MessageInfo,
+    retention: Option<u64>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let old_retention = config.history_retention;
+    config.history_retention = retention;
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_history_retention")
+        .add_attribute("sender", info.sender)
+        .add_attribute(
+            "retention",
+            retention.map_or("none".to_string(), |r| r.to_string()),
+        )
+        .add_event(config_event(
+            "history_retention",
+            &old_retention,
+            &retention,
+        )?))
+}
+
+pub fn execute_set_progression(
+    deps: DepsMut,
+    info: MessageInfo,
+    game_server: String,
+    level_thresholds: Vec<u64>,
+    max_level: u32,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if max_level == 0 || level_thresholds.windows(2).any(|w| w[0] >= w[1]) {
+        return Err(ContractError::InvalidProgression {});
+    }
+
+    let progression = Progression {
+        game_server: deps.api.addr_validate(&game_server)?,
+        level_thresholds,
+        max_level,
+    };
+    let mut config = CONFIG.load(deps.storage)?;
+    let old_progression = config.progression.replace(progression);
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_progression")
+        .add_attribute("sender", info.sender)
+        .add_attribute("game_server", game_server)
+        .add_attribute("max_level", max_level.to_string())
+        .add_event(config_event(
+            "progression",
+            &old_progression,
+            &config.progression,
+        )?))
+}
+
+pub fn execute_add_experience(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    amount: u64,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let config = CONFIG.load(deps.storage)?;
+    let progression = config
+        .progression
+        .ok_or(ContractError::ProgressionNotSet {})?;
+    if info.sender != progression.game_server {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let old_token = cw721_contract
+        .tokens
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::TokenNotFound {})?;
+
+    let old_experience = EXPERIENCE
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+    let experience = old_experience
+        .checked_add(amount)
+        .ok_or_else(|| StdError::generic_err("experience overflow"))?;
+    EXPERIENCE.save(deps.storage, &token_id, &experience)?;
+
+    // levels above what the experience reaches, set before the progression
+    // or beyond its thresholds, are never lowered
+    let old_level = level_trait_of(&old_token.extension);
+    let current_level = old_level
+        .as_ref()
+        .and_then(|level| level.parse::<u32>().ok())
+        .unwrap_or(1)
+        .min(progression.max_level);
+    let level = progression.level(experience).max(current_level);
+
+    let mut response = Response::new()
+        .add_attribute("action", "add_experience")
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("amount", amount.to_string())
+        .add_attribute("experience", experience.to_string())
+        .add_attribute("level", level.to_string());
+
+    // keep the level trait in sync with the experience
+    if old_level != Some(level.to_string()) {
+        let mut new_token = old_token.clone();
+        new_token.extension = set_level_trait(new_token.extension, level.to_string());
+        cw721_contract
+            .tokens
+            .save(deps.storage, &token_id, &new_token)?;
+        let change = record_metadata_change(
+            deps.storage,
+            &env,
+            &info.sender,
+            &token_id,
+            &old_token,
+            &new_token,
+        )?;
+        response = response
+            .add_event(update_event(&token_id, &change)?)
+            .add_event(level_event(&token_id, old_level, &level.to_string()));
+    }
+
+    Ok(response)
+}
+
+/// Fixed point precision of rarity scores
+pub const RARITY_PRECISION: u64 = 1_000;
+
+pub fn execute_recompute_rarity(
+    deps: DepsMut,
+    info: MessageInfo,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    if !config.frozen {
+        return Err(ContractError::ContractNotFrozen {});
+    }
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+    let tokens: StdResult<Vec<_>> = cw721_contract
+        .tokens
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect();
+    let tokens = tokens?;
+
+    let num_tokens = cw721_contract.token_count(deps.storage)?;
+    let mut last_token_id = None;
+    for (key, token) in tokens {
+        let token_id = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+        let mut score = 0u64;
+        for (trait_type, value) in token_traits_of(&token.extension) {
+            let count = TRAIT_COUNTS
+                .may_load(deps.storage, (&trait_type, &value))?
+                .unwrap_or(1);
+            score += num_tokens * RARITY_PRECISION / count;
+        }
+
+        if let Some(old_score) = RARITY_SCORES.may_load(deps.storage, &token_id)? {
+            RARITY_RANKING.remove(deps.storage, (old_score.into(), &token_id));
+        }
+        RARITY_SCORES.save(deps.storage, &token_id, &score)?;
+        RARITY_RANKING.save(
+            deps.storage,
+            (score.into(), &token_id),
+            &RarityResponse {
+                token_id: token_id.clone(),
+                score,
+            },
+        )?;
+        last_token_id = Some(token_id);
+    }
+
+    let last_token_id = last_token_id.unwrap_or_default();
+    Ok(Response::new()
+        .add_attribute("action", "recompute_rarity")
+        .add_attribute("sender", info.sender)
+        .add_attribute("last_token_id", &last_token_id)
+        .add_event(recompute_rarity_event(num_tokens, &last_token_id)))
+}
+
+// Backfills the trait index and holding times of a page of tokens minted
+// before 0.10.0, and fixes the token count once the last page is done.
+// Minting and burning are rejected until then
+pub fn execute_reindex(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    limit: Option<u32>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut progress = REINDEX
+        .may_load(deps.storage)?
+        .ok_or(ContractError::NoReindex {})?;
+
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = progress.last_token_id.clone().map(Bound::exclusive);
+    let tokens: StdResult<Vec<_>> = cw721_contract
+        .tokens
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .collect();
+    let tokens = tokens?;
+    let done = tokens.len() < limit;
+
+    for (key, token) in tokens {
+        let token_id = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+        update_trait_index(deps.storage, &token_id, &token.extension)?;
+        // when legacy tokens were acquired is unknown, they count from now
+        if !HELD_SINCE.has(deps.storage, &token_id) {
+            HELD_SINCE.save(deps.storage, &token_id, &env.block.time)?;
+        }
+        progress.token_count += 1;
+        progress.last_token_id = Some(token_id);
+    }
+
+    if done {
+        cw721_contract
+            .token_count
+            .save(deps.storage, &progress.token_count)?;
+        REINDEX.remove(deps.storage);
+    } else {
+        REINDEX.save(deps.storage, &progress)?;
+    }
+
+    let last_token_id = progress.last_token_id.unwrap_or_default();
+    Ok(Response::new()
+        .add_attribute("action", "reindex")
+        .add_attribute("sender", info.sender)
+        .add_attribute("last_token_id", &last_token_id)
+        .add_event(reindex_event(&last_token_id, progress.token_count, done)))
+}
+
+pub fn execute_set_guardian(
+    deps: DepsMut,
+    info: MessageInfo,
+    guardian: Option<String>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let guardian = guardian
+        .map(|guardian| deps.api.addr_validate(&guardian))
+        .transpose()?;
+    let mut pause = PAUSE.may_load(deps.storage)?.unwrap_or_default();
+    let old_guardian = std::mem::replace(&mut pause.guardian, guardian);
+    PAUSE.save(deps.storage, &pause)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_guardian")
+        .add_attribute("sender", info.sender)
+        .add_event(config_event("guardian", &old_guardian, &pause.guardian)?))
+}
+
+pub fn execute_set_paused(
+    deps: DepsMut,
+    info: MessageInfo,
+    paused: bool,
+) -> Result<Response, ContractError> {
+    let mut pause = PAUSE.may_load(deps.storage)?.unwrap_or_default();
+    if pause.guardian.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let old_paused = pause.paused;
+    pause.paused = paused;
+    PAUSE.save(deps.storage, &pause)?;
+
+    Ok(Response::new()
+        .add_attribute("action", if paused { "pause" } else { "unpause" })
+        .add_attribute("sender", info.sender)
+        .add_event(config_event("paused", &old_paused, &paused)?))
+}
+
+pub fn execute_set_paused_operations(
+    deps: DepsMut,
+    info: MessageInfo,
+    operations: Vec<Operation>,
+) -> Result<Response, ContractError> {
+    let mut pause = PAUSE.may_load(deps.storage)?.unwrap_or_default();
+    if pause.guardian.as_ref() != Some(&info.sender) {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut paused_operations: Vec<Operation> = vec![];
+    for operation in operations {
+        if !paused_operations.contains(&operation) {
+            paused_operations.push(operation);
+        }
+    }
+    let old_operations = std::mem::replace(&mut pause.paused_operations, paused_operations);
+    PAUSE.save(deps.storage, &pause)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_paused_operations")
+        .add_attribute("sender", info.sender)
+        .add_event(config_event(
+            "paused_operations",
+            &old_operations,
+            &pause.paused_operations,
+        )?))
+}
+
+pub fn execute_set_transferable(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: Option<String>,
+    transferable: bool,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let old_transferable = match &token_id {
+        Some(token_id) => {
+            if cw721_contract
+                .tokens
+                .may_load(deps.storage, token_id)?
+                .is_none()
+            {
+                return Err(ContractError::TokenNotFound {});
+            }
+            let old = TRANSFERABLE
+                .may_load(deps.storage, token_id)?
+                .unwrap_or(config.transferable);
+            TRANSFERABLE.save(deps.storage, token_id, &transferable)?;
+            old
+        }
+        None => {
+            let old = config.transferable;
+            config.transferable = transferable;
+            CONFIG.save(deps.storage, &config)?;
+            old
+        }
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "set_transferable")
+        .add_attribute("sender", info.sender)
+        .add_attribute("transferable", transferable.to_string())
+        .add_event(transferable_event(
+            token_id.as_deref(),
+            old_transferable,
+            transferable,
+        )))
+}
+
+pub fn execute_recover_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    recipient: String,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    // transferable tokens are moved by their owner
+    let config = CONFIG.load(deps.storage)?;
+    let transferable = TRANSFERABLE
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or(config.transferable);
+    if transferable {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    if stakes().may_load(deps.storage, &token_id)?.is_some() {
+        return Err(ContractError::TokenStaked {});
+    }
+    check_not_rented(deps.storage, &env, &token_id)?;
+
+    let recipient = deps.api.addr_validate(&recipient)?;
+    let mut token = cw721_contract
+        .tokens
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::TokenNotFound {})?;
+    let old_owner = std::mem::replace(&mut token.owner, recipient.clone());
+    token.approvals = vec![];
+    cw721_contract
+        .tokens
+        .save(deps.storage, &token_id, &token)?;
+    TRANSFER_LOCKS.remove(deps.storage, &token_id);
+    record_owner_change(deps.storage, &token_id, env.block.time)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "recover_token")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("recipient", recipient.to_string())
+        .add_event(transfer_event(&token_id, &old_owner, &recipient)))
+}
+
+pub fn execute_set_transfer_lock(
+    deps: DepsMut,
+    info: MessageInfo,
+    duration: Option<Duration>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let old_duration = std::mem::replace(&mut config.transfer_lock, duration);
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_transfer_lock")
+        .add_attribute("sender", info.sender)
+        .add_event(config_event(
+            "transfer_lock",
+            &old_duration,
+            &config.transfer_lock,
+        )?))
+}
+
+pub fn execute_stake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    if token.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let stakes = stakes();
+    if stakes.may_load(deps.storage, &token_id)?.is_some() {
+        return Err(ContractError::TokenStaked {});
+    }
+    let stake = Stake {
+        owner: info.sender.clone(),
+        since: env.block.time,
+    };
+    stakes.save(deps.storage, &token_id, &stake)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "stake")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("token_id", token_id.clone())
+        .add_event(stake_event(&token_id, &info.sender)))
+}
+
+pub fn execute_unstake(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let stakes = stakes();
+    let stake = stakes
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::TokenNotStaked {})?;
+    if stake.owner != info.sender {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    stakes.remove(deps.storage, &token_id)?;
+    let staked_seconds = env.block.time.seconds() - stake.since.seconds();
+    STAKED_SECONDS.update(deps.storage, &token_id, |total| -> StdResult<u64> {
+        Ok(total.unwrap_or_default() + staked_seconds)
+    })?;
+
+    Ok(Response::new()
+        .add_attribute("action", "unstake")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("staked_seconds", staked_seconds.to_string())
+        .add_event(unstake_event(&token_id, &info.sender, staked_seconds)))
+}
+
+// Derives a level from the input levels, clamped to the highest level the
+// progression thresholds reach
+fn inherited_level(
+    inheritance: LevelInheritance,
+    levels: &[u32],
+    progression: &Option<Progression>,
+) -> u32 {
+    // summed as u64 so large levels cannot overflow, at most u32::MAX inputs
+    let sum = levels.iter().map(|level| *level as u64).sum::<u64>();
+    let level = match inheritance {
+        LevelInheritance::Max => levels.iter().copied().max().unwrap_or(1),
+        LevelInheritance::Sum => sum.min(u32::MAX as u64) as u32,
+        LevelInheritance::Average => (sum / levels.len().max(1) as u64) as u32,
+    };
+    match progression {
+        Some(progression) => level.max(1).min(progression.level(u64::MAX)),
+        None => level.max(1),
+    }
+}
+
+pub fn execute_fuse(
+    mut deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_ids: Vec<String>,
+    recipe_id: String,
+) -> Result<Response, ContractError> {
+    let recipe = RECIPES
+        .may_load(deps.storage, &recipe_id)?
+        .ok_or(ContractError::RecipeNotFound {})?;
+
+    let mut distinct = token_ids.clone();
+    distinct.sort();
+    distinct.dedup();
+    if distinct.len() != token_ids.len() || token_ids.len() != recipe.input_count as usize {
+        return Err(ContractError::InvalidRecipeInputs {
+            expected: recipe.input_count,
+        });
+    }
+
+    let cw721_contract = RestNFTContract::default();
+    let mut levels = vec![];
+    let mut response = Response::new();
+    for token_id in &token_ids {
+        let token = cw721_contract.tokens.load(deps.storage, token_id)?;
+        let level = level_trait_of(&token.extension)
+            .and_then(|level| level.parse::<u32>().ok())
+            .unwrap_or(1);
+        levels.push(level);
+        response = response.add_event(burn_token(deps.branch(), &env, &info, token_id)?);
+    }
+
+    // skips ids the minter already took
+    let mut fusions = FUSION_COUNT.may_load(deps.storage)?.unwrap_or_default();
+    let token_id = loop {
+        fusions += 1;
+        let token_id = format!("fused-{}", fusions);
+        if cw721_contract
+            .tokens
+            .may_load(deps.storage, &token_id)?
+            .is_none()
+        {
+            break token_id;
+        }
+    };
+    FUSION_COUNT.save(deps.storage, &fusions)?;
+
+    let config = CONFIG.load(deps.storage)?;
+    let mut extension = Some(recipe.metadata);
+    if let Some(inheritance) = recipe.level_inheritance {
+        let level = inherited_level(inheritance, &levels, &config.progression);
+        extension = set_level_trait(extension, level.to_string());
+        save_level_experience(
+            deps.storage,
+            &token_id,
+            &level.to_string(),
+            &config.progression,
+        )?;
+    }
+
+    let mint = mint_token(
+        deps.storage,
+        &env,
+        &token_id,
+        &info.sender,
+        recipe.token_uri,
+        extension,
+    )?;
+
+    Ok(response
+        .add_attribute("action", "fuse")
+        .add_attribute("sender", info.sender)
+        .add_attribute("recipe_id", recipe_id.clone())
+        .add_attribute("token_id", token_id.clone())
+        .add_event(mint)
+        .add_event(fuse_event(&token_id, &recipe_id, &token_ids)))
+}
+
+pub fn execute_set_recipe(
+    deps: DepsMut,
+    info: MessageInfo,
+    recipe_id: String,
+    recipe: Option<Recipe>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let old_recipe = RECIPES.may_load(deps.storage, &recipe_id)?;
+    match &recipe {
+        Some(recipe) => {
+            if recipe.input_count == 0 {
+                return Err(ContractError::InvalidRecipeInputs { expected: 0 });
+            }
+            RECIPES.save(deps.storage, &recipe_id, recipe)?
+        }
+        None => {
+            if old_recipe.is_none() {
+                return Err(ContractError::RecipeNotFound {});
+            }
+            RECIPES.remove(deps.storage, &recipe_id)
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute(
+            "action",
+            if recipe.is_some() {
+                "set_recipe"
+            } else {
+                "remove_recipe"
+            },
+        )
+        .add_attribute("sender", info.sender)
+        .add_attribute("recipe_id", recipe_id.clone())
+        .add_event(recipe_event(&recipe_id, &old_recipe, &recipe)?))
+}
+
+// Deterministic entropy of a breeding, from the block and the parents
+fn breed_entropy(env: &Env, parent_a: &str, parent_b: &str, count: u64) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(env.block.chain_id.as_bytes());
+    hasher.update(env.block.height.to_be_bytes());
+    hasher.update(env.block.time.nanos().to_be_bytes());
+    hasher.update(parent_a.as_bytes());
+    hasher.update(parent_b.as_bytes());
+    hasher.update(count.to_be_bytes());
+    hasher.finalize().into()
+}
+
+pub fn execute_breed(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    parent_a: String,
+    parent_b: String,
+) -> Result<Response, ContractError> {
+    let config = CONFIG.load(deps.storage)?;
+    let cooldown = config
+        .breed_cooldown
+        .ok_or(ContractError::BreedingDisabled {})?;
+    if parent_a == parent_b {
+        return Err(ContractError::InvalidParents {});
+    }
+
+    let cw721_contract = RestNFTContract::default();
+    let mut parents = vec![];
+    let mut generation = 0;
+    for token_id in [&parent_a, &parent_b] {
+        let token = cw721_contract.tokens.load(deps.storage, token_id)?;
+        _check_can_send(&cw721_contract, deps.as_ref(), &env, &info, &token)?;
+        if let Some(expires) = BREED_COOLDOWNS.may_load(deps.storage, token_id)? {
+            if !expires.is_expired(&env.block) {
+                return Err(ContractError::BreedCooldown { expires });
+            }
+        }
+
+        let lineage = LINEAGE
+            .may_load(deps.storage, token_id)?
+            .unwrap_or_default();
+        generation = generation.max(lineage.generation + 1);
+        parents.push(token);
+    }
+
+    // skips ids the minter already took
+    let mut count = BREED_COUNT.may_load(deps.storage)?.unwrap_or_default();
+    let token_id = loop {
+        count += 1;
+        let token_id = format!("bred-{}", count);
+        if cw721_contract
+            .tokens
+            .may_load(deps.storage, &token_id)?
+            .is_none()
+        {
+            break token_id;
+        }
+    };
+    BREED_COUNT.save(deps.storage, &count)?;
+
+    // each trait comes from one of the parents, picked by an entropy bit.
+    // Levels are not inherited, children start over
+    let entropy = breed_entropy(&env, &parent_a, &parent_b, count);
+    let traits_a = attributes_of(&parents[0].extension);
+    let traits_b = attributes_of(&parents[1].extension);
+    let mut trait_types: Vec<String> = traits_a
+        .iter()
+        .chain(traits_b.iter())
+        .map(|t| t.trait_type.clone())
+        .filter(|trait_type| trait_type != LEVEL_TRAIT)
+        .collect();
+    trait_types.sort();
+    trait_types.dedup();
+
+    let mut attributes = vec![];
+    for (i, trait_type) in trait_types.iter().enumerate() {
+        let bit = entropy[(i / 8) % entropy.len()] >> (i % 8) & 1;
+        let (first, second) = if bit == 0 {
+            (&traits_a, &traits_b)
+        } else {
+            (&traits_b, &traits_a)
+        };
+        let inherited = first
+            .iter()
+            .chain(second.iter())
+            .find(|t| &t.trait_type == trait_type);
+        if let Some(inherited) = inherited {
+            attributes.push(inherited.clone());
+        }
+    }
+    let extension = Some(Metadata {
+        attributes: Some(attributes),
+        ..Metadata::default()
+    });
+
+    let mint = mint_token(deps.storage, &env, &token_id, &info.sender, None, extension)?;
+    for parent in [&parent_a, &parent_b] {
+        BREED_COOLDOWNS.save(deps.storage, parent, &cooldown.after(&env.block))?;
+    }
+    LINEAGE.save(
+        deps.storage,
+        &token_id,
+        &Lineage {
+            parents: vec![parent_a.clone(), parent_b.clone()],
+            generation,
+        },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "breed")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_event(mint)
+        .add_event(breed_event(&token_id, &parent_a, &parent_b, generation)))
+}
+
+// Attributes of the token metadata, empty when unset
+fn attributes_of(extension: &Extension) -> Vec<Trait> {
+    extension
+        .as_ref()
+        .and_then(|metadata| metadata.attributes.clone())
+        .unwrap_or_default()
+}
+
+pub fn execute_set_breeding(
+    deps: DepsMut,
+    info: MessageInfo,
+    cooldown: Option<Duration>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let mut config = CONFIG.load(deps.storage)?;
+    let old_cooldown = std::mem::replace(&mut config.breed_cooldown, cooldown);
+    CONFIG.save(deps.storage, &config)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "set_breeding")
+        .add_attribute("sender", info.sender)
+        .add_event(config_event(
+            "breed_cooldown",
+            &old_cooldown,
+            &config.breed_cooldown,
+        )?))
+}
+
+// Checks the evolution conditions of a token, returning the fee payment if any
+fn check_evolution(
+    deps: Deps,
+    env: &Env,
+    info: &MessageInfo,
+    token_id: &str,
+    token: &TokenInfo<Extension>,
+    evolution: &Evolution,
+) -> Result<Option<BankMsg>, ContractError> {
+    if let Some(min_level) = evolution.min_level {
+        let level = level_trait_of(&token.extension)
+            .and_then(|level| level.parse::<u32>().ok())
+            .unwrap_or_default();
+        if level < min_level {
+            return Err(ContractError::LevelTooLow { min_level });
+        }
+    }
+
+    // tokens received before hold times were tracked have been held long enough
+    if let Some(min_hold_seconds) = evolution.min_hold_seconds {
+        if let Some(since) = HELD_SINCE.may_load(deps.storage, token_id)? {
+            let until = checked_plus_seconds(since, min_hold_seconds)?;
+            if env.block.time < until {
+                return Err(ContractError::HeldTooShort { until });
+            }
+        }
+    }
+
+    // exactly the fee, nothing is kept beyond it
+    if info.funds != evolution.fee.iter().cloned().collect::<Vec<_>>() {
+        return Err(ContractError::Funds {});
+    }
+    match &evolution.fee {
+        Some(fee) => {
+            let minter = RestNFTContract::default().minter.load(deps.storage)?;
+            Ok(Some(BankMsg::Send {
+                to_address: minter.to_string(),
+                amount: vec![fee.clone()],
+            }))
+        }
+        None => Ok(None),
+    }
+}
+
+pub fn execute_evolve(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let old_token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    _check_can_send(&cw721_contract, deps.as_ref(), &env, &info, &old_token)?;
+
+    // the table is fixed by freeze, so evolving is allowed on frozen contracts
+    let stage = old_token
+        .extension
+        .as_ref()
+        .and_then(|metadata| metadata.name.clone())
+        .ok_or(ContractError::EvolutionNotFound {})?;
+    let evolution = EVOLUTIONS
+        .may_load(deps.storage, &stage)?
+        .ok_or(ContractError::EvolutionNotFound {})?;
+    let payment = check_evolution(
+        deps.as_ref(),
+        &env,
+        &info,
+        &token_id,
+        &old_token,
+        &evolution,
+    )?;
+
+    let mut new_token = old_token.clone();
+    new_token.extension = match level_trait_of(&old_token.extension) {
+        Some(level) if level_trait_of(&Some(evolution.metadata.clone())).is_none() => {
+            set_level_trait(Some(evolution.metadata.clone()), level)
+        }
+        _ => Some(evolution.metadata.clone()),
+    };
+    if evolution.token_uri.is_some() {
+        new_token.token_uri = evolution.token_uri.clone();
+    }
+    cw721_contract
+        .tokens
+        .save(deps.storage, &token_id, &new_token)?;
+
+    let change = record_metadata_change(
+        deps.storage,
+        &env,
+        &info.sender,
+        &token_id,
+        &old_token,
+        &new_token,
+    )?;
+
+    let mut response = Response::new();
+    if let Some(payment) = payment {
+        response = response.add_message(payment);
+    }
+    Ok(response
+        .add_attribute("action", "evolve")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("version", change.version.to_string())
+        .add_event(update_event(&token_id, &change)?)
+        .add_event(evolve_event(
+            &token_id,
+            &stage,
+            evolution.metadata.name.as_deref(),
+        )))
+}
+
+pub fn execute_set_evolution(
+    deps: DepsMut,
+    info: MessageInfo,
+    stage: String,
+    evolution: Option<Evolution>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let config = CONFIG.load(deps.storage)?;
+    if config.frozen {
+        return Err(ContractError::ContractFrozen {});
+    }
+
+    let old_evolution = EVOLUTIONS.may_load(deps.storage, &stage)?;
+    match &evolution {
+        Some(evolution) => EVOLUTIONS.save(deps.storage, &stage, evolution)?,
+        None => {
+            if old_evolution.is_none() {
+                return Err(ContractError::EvolutionNotFound {});
+            }
+            EVOLUTIONS.remove(deps.storage, &stage)
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute(
+            "action",
+            if evolution.is_some() {
+                "set_evolution"
+            } else {
+                "remove_evolution"
+            },
+        )
+        .add_attribute("sender", info.sender)
+        .add_attribute("stage", stage.clone())
+        .add_event(evolution_event(&stage, &old_evolution, &evolution)?))
+}
+
+// Whether any token or cw20 balance is equipped to the token
+fn has_children(storage: &dyn Storage, token_id: &str) -> StdResult<bool> {
+    let tokens = equipped_nfts()
+        .idx
+        .parent
+        .prefix(token_id.to_string())
+        .keys(storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+    let balances = EQUIPPED_CW20
+        .prefix(token_id)
+        .keys(storage, None, None, Order::Ascending)
+        .next()
+        .is_some();
+    Ok(tokens || balances)
+}
+
+// Loads a parent token, checking the sender can send it. Equipped tokens are
+// held by the contract, so they can never be parents themselves
+fn load_parent(
+    deps: Deps,
+    env: &Env,
+    info: &MessageInfo,
+    token_id: &str,
+) -> Result<TokenInfo<Extension>, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let parent = cw721_contract.tokens.load(deps.storage, token_id)?;
+    _check_can_send(&cw721_contract, deps, env, info, &parent)?;
+    Ok(parent)
+}
+
+pub fn execute_equip(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    child_token_id: String,
+) -> Result<Response, ContractError> {
+    if token_id == child_token_id {
+        return Err(ContractError::InvalidChild {});
+    }
+    load_parent(deps.as_ref(), &env, &info, &token_id)?;
+
+    let cw721_contract = RestNFTContract::default();
+    let mut child = cw721_contract.tokens.load(deps.storage, &child_token_id)?;
+    _check_can_send(&cw721_contract, deps.as_ref(), &env, &info, &child)?;
+    if stakes().may_load(deps.storage, &child_token_id)?.is_some() {
+        return Err(ContractError::TokenStaked {});
+    }
+    check_not_rented(deps.storage, &env, &child_token_id)?;
+    if let Some(expires) = TRANSFER_LOCKS.may_load(deps.storage, &child_token_id)? {
+        if !expires.is_expired(&env.block) {
+            return Err(ContractError::TransferLocked { expires });
+        }
+    }
+
+    let old_owner = std::mem::replace(&mut child.owner, env.contract.address.clone());
+    child.approvals = vec![];
+    cw721_contract
+        .tokens
+        .save(deps.storage, &child_token_id, &child)?;
+    record_owner_change(deps.storage, &child_token_id, env.block.time)?;
+    let equipped = EquippedNft {
+        parent: token_id.clone(),
+        contract: env.contract.address.clone(),
+        token_id: child_token_id.clone(),
+    };
+    equipped_nfts().save(
+        deps.storage,
+        (&env.contract.address, &child_token_id),
+        &equipped,
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "equip")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("child_token_id", child_token_id.clone())
+        .add_event(transfer_event(
+            &child_token_id,
+            &old_owner,
+            &env.contract.address,
+        ))
+        .add_event(equip_event(
+            &token_id,
+            &Child::Token {
+                token_id: child_token_id.clone(),
+            },
+        )?))
+}
+
+pub fn execute_unequip(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    child: Child,
+) -> Result<Response, ContractError> {
+    let owner = load_parent(deps.as_ref(), &env, &info, &token_id)?.owner;
+    let response = Response::new()
+        .add_attribute("action", "unequip")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone());
+    let response = remove_child(deps, &env, &token_id, &child, Some(&owner), response)?;
+
+    Ok(response.add_event(unequip_event(&token_id, &child, &owner)?))
+}
+
+pub fn execute_detach(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    child: Child,
+) -> Result<Response, ContractError> {
+    load_parent(deps.as_ref(), &env, &info, &token_id)?;
+    // tokens of this contract are always returned by Unequip
+    if let Child::Token { .. } = child {
+        return Err(ContractError::InvalidChild {});
+    }
+    let response = Response::new()
+        .add_attribute("action", "detach")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone());
+    let response = remove_child(deps, &env, &token_id, &child, None, response)?;
+
+    Ok(response.add_event(detach_event(&token_id, &child)?))
+}
+
+// Removes a child from the parent, sending it to the recipient when given
+fn remove_child(
+    deps: DepsMut,
+    env: &Env,
+    token_id: &str,
+    child: &Child,
+    recipient: Option<&Addr>,
+    mut response: Response,
+) -> Result<Response, ContractError> {
+    match child {
+        Child::Token {
+            token_id: child_token_id,
+        } => {
+            remove_equipped_nft(
+                deps.storage,
+                &env.contract.address,
+                child_token_id,
+                token_id,
+            )?;
+            if let Some(owner) = recipient {
+                let cw721_contract = RestNFTContract::default();
+                let mut token = cw721_contract.tokens.load(deps.storage, child_token_id)?;
+                token.owner = owner.clone();
+                cw721_contract
+                    .tokens
+                    .save(deps.storage, child_token_id, &token)?;
+                record_owner_change(deps.storage, child_token_id, env.block.time)?;
+                response = response.add_event(transfer_event(
+                    child_token_id,
+                    &env.contract.address,
+                    owner,
+                ));
+            }
+        }
+        Child::Cw721 {
+            contract,
+            token_id: child_token_id,
+        } => {
+            let contract = deps.api.addr_validate(contract)?;
+            remove_equipped_nft(deps.storage, &contract, child_token_id, token_id)?;
+            if let Some(owner) = recipient {
+                response = response.add_message(WasmMsg::Execute {
+                    contract_addr: contract.to_string(),
+                    msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                        recipient: owner.to_string(),
+                        token_id: child_token_id.clone(),
+                    })?,
+                    funds: vec![],
+                });
+            }
+        }
+        Child::Cw20 { contract, amount } => {
+            let contract = deps.api.addr_validate(contract)?;
+            let balance = EQUIPPED_CW20
+                .may_load(deps.storage, (token_id, &contract))?
+                .unwrap_or_default();
+            if amount.is_zero() || balance < *amount {
+                return Err(ContractError::InsufficientBalance {});
+            }
+            if balance == *amount {
+                EQUIPPED_CW20.remove(deps.storage, (token_id, &contract));
+            } else {
+                EQUIPPED_CW20.save(deps.storage, (token_id, &contract), &(balance - *amount))?;
+            }
+            if let Some(owner) = recipient {
+                response = response.add_message(WasmMsg::Execute {
+                    contract_addr: contract.to_string(),
+                    msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                        recipient: owner.to_string(),
+                        amount: *amount,
+                    })?,
+                    funds: vec![],
+                });
+            }
+        }
+    }
+
+    Ok(response)
+}
+
+// Removes a token from the children of the parent
+fn remove_equipped_nft(
+    storage: &mut dyn Storage,
+    contract: &Addr,
+    token_id: &str,
+    parent: &str,
+) -> Result<(), ContractError> {
+    match equipped_nfts().may_load(storage, (contract, token_id))? {
+        Some(equipped) if equipped.parent == parent => {
+            equipped_nfts().remove(storage, (contract, token_id))?;
+            Ok(())
+        }
+        _ => Err(ContractError::ChildNotFound {}),
+    }
+}
+
+// Anyone can call the receive hooks with a forged sender, so assets are only
+// accepted from contracts the minter trusts to send them for real
+fn check_child_contract(storage: &dyn Storage, contract: &Addr) -> Result<(), ContractError> {
+    if !CHILD_CONTRACTS.has(storage, contract) {
+        return Err(ContractError::NotChildContract {});
+    }
+    Ok(())
+}
+
+pub fn execute_set_child_contract(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+    allowed: bool,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let contract = deps.api.addr_validate(&contract)?;
+    let old_allowed = CHILD_CONTRACTS.has(deps.storage, &contract);
+    if allowed {
+        CHILD_CONTRACTS.save(deps.storage, &contract, &Empty {})?;
+    } else {
+        if !old_allowed {
+            return Err(ContractError::NotChildContract {});
+        }
+        CHILD_CONTRACTS.remove(deps.storage, &contract);
+    }
+
+    Ok(Response::new()
+        .add_attribute(
+            "action",
+            if allowed {
+                "set_child_contract"
+            } else {
+                "remove_child_contract"
+            },
+        )
+        .add_attribute("sender", info.sender)
+        .add_attribute("contract", contract.to_string())
+        .add_event(child_contract_event(&contract, old_allowed, allowed)))
+}
+
+// Sender of a received asset, as the info parents are checked against
+fn receive_info(deps: Deps, sender: &str) -> StdResult<MessageInfo> {
+    Ok(MessageInfo {
+        sender: deps.api.addr_validate(sender)?,
+        funds: vec![],
+    })
+}
+
+pub fn execute_receive_nft(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Cw721ReceiveMsg,
+) -> Result<Response, ContractError> {
+    // tokens of this contract are equipped through Equip
+    if info.sender == env.contract.address {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let sender = receive_info(deps.as_ref(), &msg.sender)?;
+    let token_id = match from_binary(&msg.msg)? {
+        ReceiveMsg::Equip { token_id } => token_id,
+        ReceiveMsg::Exchange {} => {
+            return exchange_token(deps, env, info, sender.sender, msg.token_id)
+        }
+    };
+    check_child_contract(deps.storage, &info.sender)?;
+    load_parent(deps.as_ref(), &env, &sender, &token_id)?;
+
+    let equipped = EquippedNft {
+        parent: token_id.clone(),
+        contract: info.sender.clone(),
+        token_id: msg.token_id.clone(),
+    };
+    equipped_nfts().save(deps.storage, (&info.sender, &msg.token_id), &equipped)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "equip")
+        .add_attribute("sender", sender.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("child_token_id", msg.token_id.clone())
+        .add_event(equip_event(
+            &token_id,
+            &Child::Cw721 {
+                contract: info.sender.to_string(),
+                token_id: msg.token_id,
+            },
+        )?))
+}
+
+pub fn execute_receive(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    msg: Cw20ReceiveMsg,
+) -> Result<Response, ContractError> {
+    let token_id = match from_binary(&msg.msg)? {
+        ReceiveMsg::Equip { token_id } => token_id,
+        ReceiveMsg::Exchange {} => return Err(ContractError::NotExchangeSource {}),
+    };
+    check_child_contract(deps.storage, &info.sender)?;
+    let sender = receive_info(deps.as_ref(), &msg.sender)?;
+    load_parent(deps.as_ref(), &env, &sender, &token_id)?;
+
+    EQUIPPED_CW20.update(
+        deps.storage,
+        (&token_id, &info.sender),
+        |balance| -> StdResult<Uint128> { Ok(balance.unwrap_or_default() + msg.amount) },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "equip")
+        .add_attribute("sender", sender.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("amount", msg.amount)
+        .add_event(equip_event(
+            &token_id,
+            &Child::Cw20 {
+                contract: info.sender.to_string(),
+                amount: msg.amount,
+            },
+        )?))
+}
+
+// Issues the token mapped to a received source token, from the minter
+// inventory when it exists or minted otherwise
+fn exchange_token(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    recipient: Addr,
+    source_token_id: String,
+) -> Result<Response, ContractError> {
+    let source = EXCHANGE_SOURCES
+        .may_load(deps.storage, &info.sender)?
+        .ok_or(ContractError::NotExchangeSource {})?;
+    if EXCHANGES
+        .may_load(deps.storage, (&info.sender, &source_token_id))?
+        .is_some()
+    {
+        return Err(ContractError::Claimed {});
+    }
+
+    let token_id = format!(
+        "{}{}",
+        source.token_id_prefix.unwrap_or_default(),
+        source_token_id
+    );
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    let issued = match cw721_contract.tokens.may_load(deps.storage, &token_id)? {
+        Some(mut token) => {
+            if token.owner != minter || stakes().may_load(deps.storage, &token_id)?.is_some() {
+                return Err(ContractError::Claimed {});
+            }
+            check_not_rented(deps.storage, &env, &token_id)?;
+            token.owner = recipient.clone();
+            token.approvals = vec![];
+            cw721_contract
+                .tokens
+                .save(deps.storage, &token_id, &token)?;
+            record_owner_change(deps.storage, &token_id, env.block.time)?;
+            transfer_event(&token_id, &minter, &recipient)
+        }
+        None => mint_token(deps.storage, &env, &token_id, &recipient, None, None)?,
+    };
+
+    let exchange = ExchangeRecord {
+        token_id: token_id.clone(),
+        recipient: recipient.clone(),
+        time: env.block.time,
+    };
+    EXCHANGES.save(deps.storage, (&info.sender, &source_token_id), &exchange)?;
+
+    let mut response = Response::new();
+    if let Some(forward_to) = source.forward_to {
+        response = response.add_message(WasmMsg::Execute {
+            contract_addr: info.sender.to_string(),
+            msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                recipient: forward_to,
+                token_id: source_token_id.clone(),
+            })?,
+            funds: vec![],
+        });
+    }
+    Ok(response
+        .add_attribute("action", "exchange")
+        .add_attribute("sender", recipient)
+        .add_attribute("source_contract", info.sender.to_string())
+        .add_attribute("source_token_id", source_token_id.clone())
+        .add_attribute("token_id", token_id)
+        .add_event(issued)
+        .add_event(exchange_event(&info.sender, &source_token_id, &exchange)))
+}
+
+pub fn execute_set_exchange_source(
+    deps: DepsMut,
+    info: MessageInfo,
+    contract: String,
+    source: Option<ExchangeSource>,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let minter = cw721_contract.minter.load(deps.storage)?;
+    if info.sender != minter {
+        return Err(ContractError::Unauthorized {});
+    }
+
+    let contract = deps.api.addr_validate(&contract)?;
+    let old_source = EXCHANGE_SOURCES.may_load(deps.storage, &contract)?;
+    match &source {
+        Some(source) => {
+            if let Some(forward_to) = &source.forward_to {
+                deps.api.addr_validate(forward_to)?;
+            }
+            EXCHANGE_SOURCES.save(deps.storage, &contract, source)?
+        }
+        None => {
+            if old_source.is_none() {
+                return Err(ContractError::NotExchangeSource {});
+            }
+            EXCHANGE_SOURCES.remove(deps.storage, &contract)
+        }
+    }
+
+    Ok(Response::new()
+        .add_attribute(
+            "action",
+            if source.is_some() {
+                "set_exchange_source"
+            } else {
+                "remove_exchange_source"
+            },
+        )
+        .add_attribute("sender", info.sender)
+        .add_attribute("contract", contract.to_string())
+        .add_event(exchange_source_event(&contract, &old_source, &source)?))
+}
+
+pub fn execute_set_user(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    user: Option<String>,
+    expires: Expiration,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    _check_can_send(&cw721_contract, deps.as_ref(), &env, &info, &token)?;
+    // the renter is the user until the rental ends
+    check_not_rented(deps.storage, &env, &token_id)?;
+
+    let user = match user {
+        Some(user) => {
+            if expires.is_expired(&env.block) {
+                return Err(ContractError::InvalidExpiration {});
+            }
+            let user = TokenUser {
+                user: deps.api.addr_validate(&user)?,
+                expires,
+            };
+            USERS.save(deps.storage, &token_id, &user)?;
+            Some(user)
+        }
+        None => {
+            USERS.remove(deps.storage, &token_id);
+            None
+        }
+    };
+
+    Ok(Response::new()
+        .add_attribute("action", "set_user")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_event(user_event(&token_id, user.as_ref())?))
+}
+
+// Rejects changes of owner or user while the token is rented
+fn check_not_rented(storage: &dyn Storage, env: &Env, token_id: &str) -> Result<(), ContractError> {
+    if let Some(rental) = RENTALS.may_load(storage, token_id)? {
+        if !rental.expires.is_expired(&env.block) {
+            return Err(ContractError::TokenRented {
+                expires: rental.expires,
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Longest rental a listing can offer, in days
+pub const MAX_RENTAL_DAYS: u32 = 365;
+
+pub fn execute_list_for_rent(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    price_per_day: Coin,
+    max_days: u32,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    _check_can_send(&cw721_contract, deps.as_ref(), &env, &info, &token)?;
+    if max_days == 0 || max_days > MAX_RENTAL_DAYS {
+        return Err(ContractError::InvalidRentalDays {
+            max_days: MAX_RENTAL_DAYS,
+        });
+    }
+    if price_per_day.amount.is_zero() || price_per_day.denom.is_empty() {
+        return Err(ContractError::InvalidRentalPrice {});
+    }
+
+    let listing = RentalListing {
+        owner: token.owner,
+        price_per_day,
+        max_days,
+    };
+    let old_listing = RENTAL_LISTINGS.may_load(deps.storage, &token_id)?;
+    RENTAL_LISTINGS.save(deps.storage, &token_id, &listing)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "list_for_rent")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_event(rental_listing_event(
+            &token_id,
+            &old_listing,
+            &Some(listing),
+        )?))
+}
+
+pub fn execute_unlist(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let cw721_contract = RestNFTContract::default();
+    let token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    _check_can_send(&cw721_contract, deps.as_ref(), &env, &info, &token)?;
+
+    let old_listing = RENTAL_LISTINGS.may_load(deps.storage, &token_id)?;
+    if old_listing.is_none() {
+        return Err(ContractError::NotListed {});
+    }
+    RENTAL_LISTINGS.remove(deps.storage, &token_id);
+
+    Ok(Response::new()
+        .add_attribute("action", "unlist")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_event(rental_listing_event(&token_id, &old_listing, &None)?))
+}
+
+// Pays the escrowed payment of a rental to the owner
+fn rental_payout(rental: &Rental) -> BankMsg {
+    BankMsg::Send {
+        to_address: rental.owner.to_string(),
+        amount: vec![rental.payment.clone()],
+    }
+}
+
+pub fn execute_rent(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    days: u32,
+) -> Result<Response, ContractError> {
+    let listing = RENTAL_LISTINGS
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::NotListed {})?;
+    if days == 0 || days > listing.max_days {
+        return Err(ContractError::InvalidRentalDays {
+            max_days: listing.max_days,
+        });
+    }
+    check_not_rented(deps.storage, &env, &token_id)?;
+
+    let amount = listing
+        .price_per_day
+        .amount
+        .checked_mul(Uint128::from(days))
+        .map_err(StdError::from)?;
+    let payment = Coin::new(amount.u128(), listing.price_per_day.denom);
+    if info.funds != [payment.clone()] {
+        return Err(ContractError::Funds {});
+    }
+
+    // a new rental settles the unclaimed payout of the previous one
+    let mut response = Response::new();
+    if let Some(previous) = RENTALS.may_load(deps.storage, &token_id)? {
+        response = response
+            .add_message(rental_payout(&previous))
+            .add_event(rental_payout_event(&token_id, &previous));
+    }
+
+    let expires = Expiration::AtTime(checked_plus_seconds(
+        env.block.time,
+        u64::from(days) * 86400,
+    )?);
+    let rental = Rental {
+        renter: info.sender.clone(),
+        owner: listing.owner,
+        payment,
+        expires,
+    };
+    RENTALS.save(deps.storage, &token_id, &rental)?;
+    let user = TokenUser {
+        user: info.sender.clone(),
+        expires,
+    };
+    USERS.save(deps.storage, &token_id, &user)?;
+
+    Ok(response
+        .add_attribute("action", "rent")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_event(rent_event(&token_id, &rental)?)
+        .add_event(user_event(&token_id, Some(&user))?))
+}
+
+pub fn execute_claim_rental_payout(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let rental = RENTALS
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::RentalNotFound {})?;
+    if info.sender != rental.owner {
+        return Err(ContractError::Unauthorized {});
+    }
+    check_not_rented(deps.storage, &env, &token_id)?;
+    RENTALS.remove(deps.storage, &token_id);
+
+    Ok(Response::new()
+        .add_message(rental_payout(&rental))
+        .add_attribute("action", "claim_rental_payout")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_event(rental_payout_event(&token_id, &rental)))
+}
+
+/// Longest buyout auction, in seconds
+pub const MAX_AUCTION_DURATION: u64 = 30 * 86400;
+
+pub fn execute_fractionalize(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+    shares: Uint128,
+    reserve_price: Coin,
+    auction_duration: u64,
+) -> Result<Response, ContractError> {
+    if shares.is_zero() {
+        return Err(ContractError::InvalidShares {});
+    }
+    // a vault cannot be undone, so it must always be possible to buy out
+    if reserve_price.amount.is_zero() || reserve_price.denom.is_empty() {
+        return Err(ContractError::InvalidReservePrice {});
+    }
+    if auction_duration == 0 || auction_duration > MAX_AUCTION_DURATION {
+        return Err(ContractError::InvalidAuctionDuration {
+            max: MAX_AUCTION_DURATION,
+        });
+    }
+    // a bought out vault stays until all its shares are redeemed
+    if VAULTS.has(deps.storage, &token_id) {
+        return Err(ContractError::Fractionalized {});
+    }
+
+    let cw721_contract = RestNFTContract::default();
+    let mut token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    _check_can_send(&cw721_contract, deps.as_ref(), &env, &info, &token)?;
+    if stakes().may_load(deps.storage, &token_id)?.is_some() {
+        return Err(ContractError::TokenStaked {});
+    }
+    check_not_rented(deps.storage, &env, &token_id)?;
+    if let Some(expires) = TRANSFER_LOCKS.may_load(deps.storage, &token_id)? {
+        if !expires.is_expired(&env.block) {
+            return Err(ContractError::TransferLocked { expires });
+        }
+    }
+
+    // the contract holds the token until it is bought out
+    let owner = std::mem::replace(&mut token.owner, env.contract.address.clone());
+    token.approvals = vec![];
+    cw721_contract
+        .tokens
+        .save(deps.storage, &token_id, &token)?;
+    record_owner_change(deps.storage, &token_id, env.block.time)?;
+
+    let vault = Vault {
+        owner: owner.clone(),
+        total_shares: shares,
+        reserve_price,
+        auction_duration,
+        highest_bid: None,
+        auction_end: None,
+        proceeds: None,
+    };
+    VAULTS.save(deps.storage, &token_id, &vault)?;
+    SHARES.save(deps.storage, (&token_id, &owner), &shares)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "fractionalize")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("shares", shares)
+        .add_event(transfer_event(&token_id, &owner, &env.contract.address))
+        .add_event(fractionalize_event(&token_id, &vault)))
+}
+
+pub fn execute_transfer_shares(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+    recipient: String,
+    amount: Uint128,
+) -> Result<Response, ContractError> {
+    if !VAULTS.has(deps.storage, &token_id) {
+        return Err(ContractError::NotFractionalized {});
+    }
+    let recipient = deps.api.addr_validate(&recipient)?;
+
+    let balance = SHARES
+        .may_load(deps.storage, (&token_id, &info.sender))?
+        .unwrap_or_default();
+    if amount.is_zero() || balance < amount {
+        return Err(ContractError::InsufficientBalance {});
+    }
+    if balance == amount {
+        SHARES.remove(deps.storage, (&token_id, &info.sender));
+    } else {
+        SHARES.save(deps.storage, (&token_id, &info.sender), &(balance - amount))?;
+    }
+    SHARES.update(
+        deps.storage,
+        (&token_id, &recipient),
+        |balance| -> StdResult<Uint128> { Ok(balance.unwrap_or_default() + amount) },
+    )?;
+
+    Ok(Response::new()
+        .add_attribute("action", "transfer_shares")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("recipient", recipient.to_string())
+        .add_attribute("amount", amount)
+        .add_event(shares_event(&token_id, &info.sender, &recipient, amount)))
+}
+
+pub fn execute_bid_buyout(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let mut vault = VAULTS
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::NotFractionalized {})?;
+    if let Some(auction_end) = vault.auction_end {
+        if env.block.time >= auction_end {
+            return Err(ContractError::AuctionEnded {});
+        }
+    }
+
+    let denom = &vault.reserve_price.denom;
+    let amount = match &info.funds[..] {
+        [funds] if &funds.denom == denom => funds.clone(),
+        _ => return Err(ContractError::Funds {}),
+    };
+    let outbids = match &vault.highest_bid {
+        Some(highest_bid) => amount.amount > highest_bid.amount.amount,
+        None => amount.amount >= vault.reserve_price.amount,
+    };
+    if !outbids {
+        let minimum = match &vault.highest_bid {
+            Some(highest_bid) => coin(highest_bid.amount.amount.u128() + 1, denom),
+            None => vault.reserve_price.clone(),
+        };
+        return Err(ContractError::BidTooLow {
+            minimum: minimum.to_string(),
+        });
+    }
+
+    let bid = BuyoutBid {
+        bidder: info.sender.clone(),
+        amount,
+    };
+    let mut response = Response::new();
+    if let Some(outbid) = vault.highest_bid.replace(bid.clone()) {
+        response = response.add_message(BankMsg::Send {
+            to_address: outbid.bidder.to_string(),
+            amount: vec![outbid.amount],
+        });
+    }
+    if vault.auction_end.is_none() {
+        vault.auction_end = Some(checked_plus_seconds(
+            env.block.time,
+            vault.auction_duration,
+        )?);
+    }
+    VAULTS.save(deps.storage, &token_id, &vault)?;
+
+    Ok(response
+        .add_attribute("action", "bid_buyout")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_event(bid_event(&token_id, &bid)))
+}
+
+pub fn execute_settle_buyout(
+    deps: DepsMut,
+    env: Env,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let mut vault = VAULTS
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::NotFractionalized {})?;
+    let bid = match (&vault.highest_bid, vault.auction_end, &vault.proceeds) {
+        (Some(bid), Some(auction_end), None) if env.block.time >= auction_end => bid.clone(),
+        _ => return Err(ContractError::AuctionNotEnded {}),
+    };
+
+    let cw721_contract = RestNFTContract::default();
+    let mut token = cw721_contract.tokens.load(deps.storage, &token_id)?;
+    token.owner = bid.bidder.clone();
+    cw721_contract
+        .tokens
+        .save(deps.storage, &token_id, &token)?;
+    record_owner_change(deps.storage, &token_id, env.block.time)?;
+
+    vault.proceeds = Some(bid.amount.clone());
+    VAULTS.save(deps.storage, &token_id, &vault)?;
+
+    Ok(Response::new()
+        .add_attribute("action", "settle_buyout")
+        .add_attribute("sender", info.sender)
+        .add_attribute("token_id", token_id.clone())
+        .add_event(transfer_event(
+            &token_id,
+            &env.contract.address,
+            &bid.bidder,
+        ))
+        .add_event(buyout_event(&token_id, &bid)))
+}
+
+pub fn execute_redeem(
+    deps: DepsMut,
+    info: MessageInfo,
+    token_id: String,
+) -> Result<Response, ContractError> {
+    let mut vault = VAULTS
+        .may_load(deps.storage, &token_id)?
+        .ok_or(ContractError::NotFractionalized {})?;
+    let proceeds = vault
+        .proceeds
+        .clone()
+        .ok_or(ContractError::NotBoughtOut {})?;
+
+    let shares = SHARES
+        .may_load(deps.storage, (&token_id, &info.sender))?
+        .unwrap_or_default();
+    if shares.is_zero() {
+        return Err(ContractError::InsufficientBalance {});
+    }
+    SHARES.remove(deps.storage, (&token_id, &info.sender));
+
+    // redeeming against what is left gives the last holder the rounding dust
+    let payout = coin(
+        proceeds
+            .amount
+            .multiply_ratio(shares, vault.total_shares)
+            .u128(),
+        &proceeds.denom,
+    );
+    vault.total_shares = vault
+        .total_shares
+        .checked_sub(shares)
+        .map_err(StdError::from)?;
+    if vault.total_shares.is_zero() {
+        VAULTS.remove(deps.storage, &token_id);
+    } else {
+        vault.proceeds = Some(coin(
+            (proceeds.amount - payout.amount).u128(),
+            &proceeds.denom,
+        ));
+        VAULTS.save(deps.storage, &token_id, &vault)?;
+    }
+
+    let mut response = Response::new();
+    if !payout.amount.is_zero() {
+        response = response.add_message(BankMsg::Send {
+            to_address: info.sender.to_string(),
+            amount: vec![payout.clone()],
+        });
+    }
+    Ok(response
+        .add_attribute("action", "redeem")
+        .add_attribute("sender", info.sender.clone())
+        .add_attribute("token_id", token_id.clone())
+        .add_attribute("shares", shares)
+        .add_event(redeem_event(&token_id, &info.sender, shares, &payout)))
 }
@@ -0,0 +1,60 @@
+use cosmwasm_std::Storage;
+use semver::Version;
+
+use crate::error::ContractError;
+use crate::state::{COLLECTION_INFO, CONFIG, REINDEX, SALES, SALES_STATS};
+
+pub const CONTRACT_NAME: &str = "crates.io:rest-nft-base";
+pub const CONTRACT_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Name stored by cw721-base for contracts instantiated before 0.10.0
+pub const LEGACY_CONTRACT_NAME: &str = "crates.io:cw721-base";
+
+type Migration = fn(&mut dyn Storage) -> Result<(), ContractError>;
+
+/// State migrations keyed by the version introducing them, in ascending order.
+/// Each one runs when migrating from an older version.
+pub const MIGRATIONS: &[(&str, Migration)] = &[("0.10.0", migrate_0_10_0)];
+
+pub fn parse_version(version: &str) -> Result<Version, ContractError> {
+    Version::parse(version).map_err(|_| ContractError::InvalidVersion {
+        version: version.to_string(),
+    })
+}
+
+/// Runs the migrations newer than `from` up to `to`, returning their versions
+pub fn run_migrations(
+    storage: &mut dyn Storage,
+    from: &Version,
+    to: &Version,
+) -> Result<Vec<String>, ContractError> {
+    let mut applied = vec![];
+    for (version, migration) in MIGRATIONS {
+        let version = parse_version(version)?;
+        if &version > from && &version <= to {
+            migration(storage)?;
+            applied.push(version.to_string());
+        }
+    }
+    Ok(applied)
+}
+
+// Rewrites config and sales in their current layout and stores the sales
+// stats and collection info introduced with this version. Token indexes
+// are backfilled afterwards by Reindex, a page at a time
+fn migrate_0_10_0(storage: &mut dyn Storage) -> Result<(), ContractError> {
+    let config = CONFIG.load(storage)?;
+    CONFIG.save(storage, &config)?;
+
+    let sales = SALES.load(storage)?;
+    SALES.save(storage, &sales)?;
+
+    if SALES_STATS.may_load(storage)?.is_none() {
+        SALES_STATS.save(storage, &Default::default())?;
+    }
+    if COLLECTION_INFO.may_load(storage)?.is_none() {
+        COLLECTION_INFO.save(storage, &Default::default())?;
+    }
+    REINDEX.save(storage, &Default::default())?;
+    Ok(())
+}
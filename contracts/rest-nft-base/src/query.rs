@@ -1,6 +1,29 @@
-use cosmwasm_std::{Deps, StdResult};
+use cosmwasm_std::{Deps, Env, Order, StdError, StdResult};
+use cw721::{AllNftInfoResponse, Approval, Cw721Query, NftInfoResponse, TokensResponse};
+use cw_storage_plus::{Bound, PrimaryKey, U64Key};
+use rest_nft::msg::{
+    AllNftInfoBatchResponse, EquippedResponse, EvolutionInfo, EvolutionsResponse,
+    ExchangeSourceInfo, ExchangeSourcesResponse, ExchangedResponse, ExperienceResponse,
+    LineageResponse, MetadataHistoryResponse, NftInfoRangeResponse, PauseStatusResponse,
+    RarityRankingResponse, RarityResponse, RecipeInfo, RecipesResponse, RentalListingInfo,
+    RentalListingsResponse, RentalResponse, SalesHistoryResponse, SalesStatsResponse, ShareBalance,
+    ShareHoldersResponse, SharesResponse, StakeInfoResponse, TokenAllNftInfo, TokenNftInfo,
+    TokenWithInfo, TokensWithInfoResponse, TraitCount, TraitCountsResponse, TransferLockResponse,
+    TransferableResponse, UserOfResponse,
+};
+use rest_nft::state::{
+    Child, CollectionInfo, Evolution, Extension, Recipe, RestNFTContract, Vault,
+};
 
-use crate::state::{Config, Sales, CONFIG, SALES};
+use crate::state::{
+    equipped_nfts, stakes, token_traits, Config, Sales, BREED_COOLDOWNS, COLLECTION_INFO, CONFIG,
+    EQUIPPED_CW20, EVOLUTIONS, EXCHANGES, EXCHANGE_SOURCES, EXPERIENCE, LINEAGE, METADATA_HISTORY,
+    PAUSE, RARITY_RANKING, RARITY_SCORES, RECIPES, RENTALS, RENTAL_LISTINGS, SALES, SALES_HISTORY,
+    SALES_STATS, SHARES, STAKED_SECONDS, TRAIT_COUNTS, TRANSFERABLE, TRANSFER_LOCKS, USERS, VAULTS,
+};
+
+pub const DEFAULT_LIMIT: u32 = 10;
+pub const MAX_LIMIT: u32 = 30;
 
 pub fn query_config(deps: Deps) -> StdResult<Config> {
     CONFIG.load(deps.storage)
@@ -14,3 +37,591 @@ pub fn query_frozen(deps: Deps) -> StdResult<bool> {
     let config = CONFIG.load(deps.storage)?;
     Ok(config.frozen)
 }
+
+pub fn query_collection_info(deps: Deps) -> StdResult<CollectionInfo> {
+    Ok(COLLECTION_INFO.may_load(deps.storage)?.unwrap_or_default())
+}
+
+/// Returns the stored token_uri, or `base_uri + token_id + suffix` when the token has none
+fn derive_token_uri(config: &Config, token_id: &str, token_uri: Option<String>) -> Option<String> {
+    match (token_uri, &config.base_uri) {
+        (Some(token_uri), _) => Some(token_uri),
+        (None, Some(base_uri)) => Some(format!(
+            "{}{}{}",
+            base_uri,
+            token_id,
+            config.token_uri_suffix.as_deref().unwrap_or_default()
+        )),
+        (None, None) => None,
+    }
+}
+
+pub fn query_nft_info(deps: Deps, token_id: String) -> StdResult<NftInfoResponse<Extension>> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut res = RestNFTContract::default().nft_info(deps, token_id.clone())?;
+    res.token_uri = derive_token_uri(&config, &token_id, res.token_uri);
+    Ok(res)
+}
+
+pub fn query_all_nft_info(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+    include_expired: bool,
+) -> StdResult<AllNftInfoResponse<Extension>> {
+    let config = CONFIG.load(deps.storage)?;
+    let mut res =
+        RestNFTContract::default().all_nft_info(deps, env, token_id.clone(), include_expired)?;
+    res.info.token_uri = derive_token_uri(&config, &token_id, res.info.token_uri);
+    Ok(res)
+}
+
+pub fn query_metadata_history(
+    deps: Deps,
+    token_id: String,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<MetadataHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive_int);
+
+    let changes: StdResult<Vec<_>> = METADATA_HISTORY
+        .prefix(&token_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, change)| change))
+        .collect();
+    Ok(MetadataHistoryResponse { changes: changes? })
+}
+
+pub fn query_experience(deps: Deps, token_id: String) -> StdResult<ExperienceResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    // fail for unknown tokens rather than reporting zero experience
+    RestNFTContract::default()
+        .tokens
+        .load(deps.storage, &token_id)?;
+    let experience = EXPERIENCE
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+    Ok(ExperienceResponse {
+        experience,
+        level: config.progression.map(|p| p.level(experience)),
+    })
+}
+
+pub fn query_tokens_by_trait(
+    deps: Deps,
+    trait_type: String,
+    value: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    // index entries are ordered by the (token_id, trait_type, value) primary key
+    let start = start_after.map(|token_id| {
+        Bound::exclusive((token_id.as_str(), trait_type.as_str(), value.as_str()).joined_key())
+    });
+
+    let tokens: StdResult<Vec<String>> = token_traits()
+        .idx
+        .trait_value
+        .prefix((trait_type.as_bytes().to_vec(), value.as_bytes().to_vec()))
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, token_trait)| token_trait.token_id))
+        .collect();
+    Ok(TokensResponse { tokens: tokens? })
+}
+
+pub fn query_trait_counts(
+    deps: Deps,
+    trait_type: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TraitCountsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let counts: StdResult<Vec<TraitCount>> = TRAIT_COUNTS
+        .prefix(&trait_type)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (value, count) = item?;
+            Ok(TraitCount {
+                trait_type: trait_type.clone(),
+                value: String::from_utf8(value).map_err(StdError::invalid_utf8)?,
+                count,
+            })
+        })
+        .collect();
+    Ok(TraitCountsResponse { counts: counts? })
+}
+
+pub fn query_rarity(deps: Deps, token_id: String) -> StdResult<RarityResponse> {
+    let score = RARITY_SCORES.load(deps.storage, &token_id)?;
+    Ok(RarityResponse { token_id, score })
+}
+
+pub fn query_rarity_ranking(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RarityRankingResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let end = match start_after {
+        Some(token_id) => {
+            let score = RARITY_SCORES.load(deps.storage, &token_id)?;
+            Some(Bound::exclusive(
+                (U64Key::new(score), token_id.as_str()).joined_key(),
+            ))
+        }
+        None => None,
+    };
+
+    let tokens: StdResult<Vec<_>> = RARITY_RANKING
+        .range(deps.storage, None, end, Order::Descending)
+        .take(limit)
+        .map(|item| item.map(|(_, rarity)| rarity))
+        .collect();
+    Ok(RarityRankingResponse { tokens: tokens? })
+}
+
+pub fn query_all_tokens(
+    deps: Deps,
+    start_after: Option<String>,
+    end_before: Option<String>,
+    limit: Option<u32>,
+    reverse: bool,
+) -> StdResult<TokensResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let min = start_after.map(Bound::exclusive);
+    let max = end_before.map(Bound::exclusive);
+    let order = if reverse {
+        Order::Descending
+    } else {
+        Order::Ascending
+    };
+
+    let tokens: StdResult<Vec<String>> = RestNFTContract::default()
+        .tokens
+        .range(deps.storage, min, max, order)
+        .take(limit)
+        .map(|item| {
+            let (key, _) = item?;
+            String::from_utf8(key).map_err(StdError::invalid_utf8)
+        })
+        .collect();
+    Ok(TokensResponse { tokens: tokens? })
+}
+
+pub fn query_nft_info_range(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<NftInfoRangeResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let tokens: StdResult<Vec<TokenNftInfo>> = RestNFTContract::default()
+        .tokens
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, token) = item?;
+            let token_id = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            let token_uri = derive_token_uri(&config, &token_id, token.token_uri);
+            Ok(TokenNftInfo {
+                token_id,
+                info: NftInfoResponse {
+                    token_uri,
+                    extension: token.extension,
+                },
+            })
+        })
+        .collect();
+    Ok(NftInfoRangeResponse { tokens: tokens? })
+}
+
+pub fn query_all_nft_info_batch(
+    deps: Deps,
+    env: Env,
+    token_ids: Vec<String>,
+    include_expired: bool,
+) -> StdResult<AllNftInfoBatchResponse> {
+    if token_ids.len() > MAX_LIMIT as usize {
+        return Err(StdError::generic_err(format!(
+            "Cannot query more than {} tokens at once",
+            MAX_LIMIT
+        )));
+    }
+
+    let tokens: StdResult<Vec<TokenAllNftInfo>> = token_ids
+        .into_iter()
+        .map(|token_id| {
+            let info = query_all_nft_info(deps, env.clone(), token_id.clone(), include_expired)?;
+            Ok(TokenAllNftInfo { token_id, info })
+        })
+        .collect();
+    Ok(AllNftInfoBatchResponse { tokens: tokens? })
+}
+
+pub fn query_tokens_with_info(
+    deps: Deps,
+    env: Env,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensWithInfoResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let owner_addr = deps.api.addr_validate(&owner)?;
+    let tokens: StdResult<Vec<TokenWithInfo>> = RestNFTContract::default()
+        .tokens
+        .idx
+        .owner
+        .prefix(owner_addr)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, token) = item?;
+            let token_id = String::from_utf8(key).map_err(StdError::invalid_utf8)?;
+            let approvals = token
+                .approvals
+                .iter()
+                .filter(|apr| !apr.is_expired(&env.block))
+                .map(|apr| Approval {
+                    spender: apr.spender.to_string(),
+                    expires: apr.expires,
+                })
+                .collect();
+            Ok(TokenWithInfo {
+                token_uri: derive_token_uri(&config, &token_id, token.token_uri),
+                token_id,
+                extension: token.extension,
+                approvals,
+            })
+        })
+        .collect();
+    Ok(TokensWithInfoResponse { tokens: tokens? })
+}
+
+pub fn query_sales_history(
+    deps: Deps,
+    start_after: Option<u64>,
+    limit: Option<u32>,
+) -> StdResult<SalesHistoryResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive_int);
+
+    let sales: StdResult<Vec<_>> = SALES_HISTORY
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| item.map(|(_, sale)| sale))
+        .collect();
+    Ok(SalesHistoryResponse { sales: sales? })
+}
+
+pub fn query_sales_stats(deps: Deps) -> StdResult<SalesStatsResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let stats = SALES_STATS.may_load(deps.storage)?.unwrap_or_default();
+    let token_count = RestNFTContract::default().token_count(deps.storage)?;
+    Ok(SalesStatsResponse {
+        sold: stats.sold,
+        remaining: config
+            .token_supply
+            .map(|supply| supply.saturating_sub(token_count)),
+        unique_buyers: stats.unique_buyers,
+        revenue: stats.revenue,
+    })
+}
+
+pub fn query_pause_status(deps: Deps) -> StdResult<PauseStatusResponse> {
+    let pause = PAUSE.may_load(deps.storage)?.unwrap_or_default();
+    Ok(PauseStatusResponse {
+        paused: pause.paused,
+        guardian: pause.guardian,
+        paused_operations: pause.paused_operations,
+    })
+}
+
+pub fn query_transferable(deps: Deps, token_id: Option<String>) -> StdResult<TransferableResponse> {
+    let config = CONFIG.load(deps.storage)?;
+    let transferable = match token_id {
+        Some(token_id) => TRANSFERABLE
+            .may_load(deps.storage, &token_id)?
+            .unwrap_or(config.transferable),
+        None => config.transferable,
+    };
+    Ok(TransferableResponse { transferable })
+}
+
+pub fn query_transfer_lock(
+    deps: Deps,
+    env: Env,
+    token_id: String,
+) -> StdResult<TransferLockResponse> {
+    let expires = TRANSFER_LOCKS
+        .may_load(deps.storage, &token_id)?
+        .filter(|expires| !expires.is_expired(&env.block));
+    Ok(TransferLockResponse { expires })
+}
+
+pub fn query_stake_info(deps: Deps, env: Env, token_id: String) -> StdResult<StakeInfoResponse> {
+    let stake = stakes().may_load(deps.storage, &token_id)?;
+    let mut total_staked_seconds = STAKED_SECONDS
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+    if let Some(stake) = &stake {
+        total_staked_seconds += env.block.time.seconds() - stake.since.seconds();
+    }
+    Ok(StakeInfoResponse {
+        staked: stake.is_some(),
+        owner: stake.as_ref().map(|stake| stake.owner.clone()),
+        staked_since: stake.map(|stake| stake.since),
+        total_staked_seconds,
+    })
+}
+
+pub fn query_staked_tokens(
+    deps: Deps,
+    owner: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<TokensResponse> {
+    let owner = deps.api.addr_validate(&owner)?;
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let tokens: StdResult<Vec<String>> = stakes()
+        .idx
+        .owner
+        .prefix(owner)
+        .keys(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|key| String::from_utf8(key).map_err(StdError::invalid_utf8))
+        .collect();
+    Ok(TokensResponse { tokens: tokens? })
+}
+
+pub fn query_recipe(deps: Deps, recipe_id: String) -> StdResult<Recipe> {
+    RECIPES.load(deps.storage, &recipe_id)
+}
+
+pub fn query_recipes(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RecipesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let recipes: StdResult<Vec<RecipeInfo>> = RECIPES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, recipe) = item?;
+            Ok(RecipeInfo {
+                recipe_id: String::from_utf8(key).map_err(StdError::invalid_utf8)?,
+                recipe,
+            })
+        })
+        .collect();
+    Ok(RecipesResponse { recipes: recipes? })
+}
+
+pub fn query_lineage(deps: Deps, env: Env, token_id: String) -> StdResult<LineageResponse> {
+    let lineage = LINEAGE
+        .may_load(deps.storage, &token_id)?
+        .unwrap_or_default();
+    let cooldown = BREED_COOLDOWNS
+        .may_load(deps.storage, &token_id)?
+        .filter(|expires| !expires.is_expired(&env.block));
+    Ok(LineageResponse {
+        token_id,
+        lineage,
+        cooldown,
+    })
+}
+
+pub fn query_evolution(deps: Deps, stage: String) -> StdResult<Evolution> {
+    EVOLUTIONS.load(deps.storage, &stage)
+}
+
+pub fn query_evolutions(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<EvolutionsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let evolutions: StdResult<Vec<EvolutionInfo>> = EVOLUTIONS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, evolution) = item?;
+            Ok(EvolutionInfo {
+                stage: String::from_utf8(key).map_err(StdError::invalid_utf8)?,
+                evolution,
+            })
+        })
+        .collect();
+    Ok(EvolutionsResponse {
+        evolutions: evolutions?,
+    })
+}
+
+pub fn query_equipped(deps: Deps, env: Env, token_id: String) -> StdResult<EquippedResponse> {
+    let parent = equipped_nfts()
+        .may_load(deps.storage, (&env.contract.address, &token_id))?
+        .map(|equipped| equipped.parent);
+
+    let mut children: Vec<Child> = equipped_nfts()
+        .idx
+        .parent
+        .prefix(token_id.clone())
+        .range(deps.storage, None, None, Order::Ascending)
+        .map(|item| {
+            let (_, equipped) = item?;
+            Ok(if equipped.contract == env.contract.address {
+                Child::Token {
+                    token_id: equipped.token_id,
+                }
+            } else {
+                Child::Cw721 {
+                    contract: equipped.contract.to_string(),
+                    token_id: equipped.token_id,
+                }
+            })
+        })
+        .collect::<StdResult<_>>()?;
+    for item in EQUIPPED_CW20
+        .prefix(&token_id)
+        .range(deps.storage, None, None, Order::Ascending)
+    {
+        let (key, amount) = item?;
+        children.push(Child::Cw20 {
+            contract: String::from_utf8(key).map_err(StdError::invalid_utf8)?,
+            amount,
+        });
+    }
+
+    Ok(EquippedResponse { parent, children })
+}
+
+pub fn query_exchanged(
+    deps: Deps,
+    source_contract: String,
+    source_token_id: String,
+) -> StdResult<ExchangedResponse> {
+    let source_contract = deps.api.addr_validate(&source_contract)?;
+    let exchange = EXCHANGES.may_load(deps.storage, (&source_contract, &source_token_id))?;
+    Ok(ExchangedResponse { exchange })
+}
+
+pub fn query_exchange_sources(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ExchangeSourcesResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let sources: StdResult<Vec<ExchangeSourceInfo>> = EXCHANGE_SOURCES
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, source) = item?;
+            Ok(ExchangeSourceInfo {
+                contract: String::from_utf8(key).map_err(StdError::invalid_utf8)?,
+                source,
+            })
+        })
+        .collect();
+    Ok(ExchangeSourcesResponse { sources: sources? })
+}
+
+pub fn query_user_of(deps: Deps, env: Env, token_id: String) -> StdResult<UserOfResponse> {
+    let user = USERS
+        .may_load(deps.storage, &token_id)?
+        .filter(|user| !user.expires.is_expired(&env.block));
+    Ok(UserOfResponse {
+        user: user.as_ref().map(|user| user.user.clone()),
+        expires: user.map(|user| user.expires),
+    })
+}
+
+pub fn query_rental(deps: Deps, token_id: String) -> StdResult<RentalResponse> {
+    Ok(RentalResponse {
+        listing: RENTAL_LISTINGS.may_load(deps.storage, &token_id)?,
+        rental: RENTALS.may_load(deps.storage, &token_id)?,
+    })
+}
+
+pub fn query_rental_listings(
+    deps: Deps,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<RentalListingsResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let listings: StdResult<Vec<RentalListingInfo>> = RENTAL_LISTINGS
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, listing) = item?;
+            Ok(RentalListingInfo {
+                token_id: String::from_utf8(key).map_err(StdError::invalid_utf8)?,
+                listing,
+            })
+        })
+        .collect();
+    Ok(RentalListingsResponse {
+        listings: listings?,
+    })
+}
+
+pub fn query_vault(deps: Deps, token_id: String) -> StdResult<Vault> {
+    VAULTS.load(deps.storage, &token_id)
+}
+
+pub fn query_shares(deps: Deps, token_id: String, holder: String) -> StdResult<SharesResponse> {
+    let vault = VAULTS.load(deps.storage, &token_id)?;
+    let holder = deps.api.addr_validate(&holder)?;
+    let balance = SHARES
+        .may_load(deps.storage, (&token_id, &holder))?
+        .unwrap_or_default();
+    Ok(SharesResponse {
+        balance,
+        total_shares: vault.total_shares,
+    })
+}
+
+pub fn query_share_holders(
+    deps: Deps,
+    token_id: String,
+    start_after: Option<String>,
+    limit: Option<u32>,
+) -> StdResult<ShareHoldersResponse> {
+    let limit = limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT) as usize;
+    let start = start_after.map(Bound::exclusive);
+
+    let holders: StdResult<Vec<ShareBalance>> = SHARES
+        .prefix(&token_id)
+        .range(deps.storage, start, None, Order::Ascending)
+        .take(limit)
+        .map(|item| {
+            let (key, balance) = item?;
+            Ok(ShareBalance {
+                holder: String::from_utf8(key).map_err(StdError::invalid_utf8)?,
+                balance,
+            })
+        })
+        .collect();
+    Ok(ShareHoldersResponse { holders: holders? })
+}
@@ -1,4 +1,11 @@
-use cw_storage_plus::Item;
+use cosmwasm_std::{Addr, Coin, Empty, Timestamp, Uint128};
+use cw0::{Duration, Expiration};
+use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex, U64Key};
+use rest_nft::msg::RarityResponse;
+use rest_nft::state::{
+    CollectionInfo, Evolution, ExchangeRecord, ExchangeSource, Lineage, MetadataChange, Operation,
+    Recipe, Rental, RentalListing, SaleRecord, Vault,
+};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -9,12 +16,308 @@ pub struct Config {
     pub frozen: bool,
     pub buy_amount: u64,
     pub available: bool,
+    /// Prefix used to derive token_uri for tokens minted without one
+    pub base_uri: Option<String>,
+    /// Appended after the token_id when deriving token_uri, e.g. ".json"
+    pub token_uri_suffix: Option<String>,
+    /// Number of metadata history entries kept per token, None keeps all
+    pub history_retention: Option<u64>,
+    /// Experience based leveling, None disables AddExperience
+    pub progression: Option<Progression>,
+    /// Collection default, tokens can override it. Soulbound when false
+    #[serde(default = "default_transferable")]
+    pub transferable: bool,
+    /// How long tokens bought through Buy cannot be transferred or sent
+    pub transfer_lock: Option<Duration>,
+    /// Parent cooldown after breeding, None disables Breed
+    pub breed_cooldown: Option<Duration>,
+}
+
+fn default_transferable() -> bool {
+    true
+}
+
+/// Config set through migrate. The frozen flag is kept from the stored
+/// config so a migration can never unfreeze the contract.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct MigrateConfig {
+    pub token_supply: Option<u64>,
+    pub buy_amount: u64,
+    pub available: bool,
+    pub base_uri: Option<String>,
+    pub token_uri_suffix: Option<String>,
+    pub history_retention: Option<u64>,
+    pub progression: Option<Progression>,
+    #[serde(default = "default_transferable")]
+    pub transferable: bool,
+    pub transfer_lock: Option<Duration>,
+    pub breed_cooldown: Option<Duration>,
+}
+
+impl MigrateConfig {
+    pub fn into_config(self, frozen: bool) -> Config {
+        Config {
+            token_supply: self.token_supply,
+            frozen,
+            buy_amount: self.buy_amount,
+            available: self.available,
+            base_uri: self.base_uri,
+            token_uri_suffix: self.token_uri_suffix,
+            history_retention: self.history_retention,
+            progression: self.progression,
+            transferable: self.transferable,
+            transfer_lock: self.transfer_lock,
+            breed_cooldown: self.breed_cooldown,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Progression {
+    /// The only address allowed to add experience
+    pub game_server: Addr,
+    /// Total experience needed for each level, starting at level 1
+    pub level_thresholds: Vec<u64>,
+    pub max_level: u32,
+}
+
+impl Progression {
+    /// Level reached with the given experience, at least 1 and at most max_level
+    pub fn level(&self, experience: u64) -> u32 {
+        let reached = self
+            .level_thresholds
+            .iter()
+            .take_while(|threshold| **threshold <= experience)
+            .count() as u32;
+        reached.max(1).min(self.max_level)
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Sales {
-    pub count: u64,
+    /// Id of the last token sold through Buy, where the next sale starts looking
+    #[serde(alias = "count")]
+    pub last_token_id: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct SalesStats {
+    pub sold: u64,
+    pub unique_buyers: u64,
+    /// Total revenue per denom
+    pub revenue: Vec<Coin>,
+}
+
+/// Circuit breaker state, missing until a guardian is first set
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct PauseState {
+    /// The only address allowed to pause and unpause
+    pub guardian: Option<Addr>,
+    pub paused: bool,
+    /// Operations blocked while paused
+    pub paused_operations: Vec<Operation>,
+}
+
+impl Default for PauseState {
+    fn default() -> Self {
+        PauseState {
+            guardian: None,
+            paused: false,
+            paused_operations: Operation::ALL.to_vec(),
+        }
+    }
 }
 
 pub const CONFIG: Item<Config> = Item::new("config");
 pub const SALES: Item<Sales> = Item::new("sales");
+pub const SALES_STATS: Item<SalesStats> = Item::new("sales_stats");
+/// Sales ledger keyed by sale id
+pub const SALES_HISTORY: Map<U64Key, SaleRecord> = Map::new("sales_history");
+/// Number of purchases made by each buyer
+pub const BUYERS: Map<&Addr, u64> = Map::new("buyers");
+pub const COLLECTION_INFO: Item<CollectionInfo> = Item::new("collection_info");
+pub const PAUSE: Item<PauseState> = Item::new("pause");
+/// Per token overrides of the collection transferable flag
+pub const TRANSFERABLE: Map<&str, bool> = Map::new("transferable");
+/// Post-purchase transfer locks of tokens bought through Buy
+pub const TRANSFER_LOCKS: Map<&str, Expiration> = Map::new("transfer_locks");
+/// Usage right delegated by the owner of a token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenUser {
+    pub user: Addr,
+    pub expires: Expiration,
+}
+
+/// Delegated user of each token, cleared on transfer
+pub const USERS: Map<&str, TokenUser> = Map::new("users");
+/// Rental listings, removed when the token changes owner
+pub const RENTAL_LISTINGS: Map<&str, RentalListing> = Map::new("rental_listings");
+/// Current rental of each token, kept until its payout is claimed
+pub const RENTALS: Map<&str, Rental> = Map::new("rentals");
+/// Time each token was received by its current owner, missing for tokens
+/// received before it was tracked
+pub const HELD_SINCE: Map<&str, Timestamp> = Map::new("held_since");
+
+/// Latest metadata version of each token, bumped on every metadata change
+pub const METADATA_VERSION: Map<&str, u64> = Map::new("metadata_version");
+/// Metadata change log keyed by (token_id, version)
+pub const METADATA_HISTORY: Map<(&str, U64Key), MetadataChange> = Map::new("metadata_history");
+/// Accumulated experience of each token
+pub const EXPERIENCE: Map<&str, u64> = Map::new("experience");
+
+/// A single (trait_type, value) attribute of a token
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct TokenTrait {
+    pub token_id: String,
+    pub trait_type: String,
+    pub value: String,
+}
+
+/// (trait_type, value, pk) index key
+pub type TraitValueKey = (Vec<u8>, Vec<u8>, Vec<u8>);
+
+pub struct TokenTraitIndexes<'a> {
+    pub trait_value: MultiIndex<'a, TraitValueKey, TokenTrait>,
+}
+
+impl<'a> IndexList<TokenTrait> for TokenTraitIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<TokenTrait>> + '_> {
+        let v: Vec<&dyn Index<TokenTrait>> = vec![&self.trait_value];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn token_trait_value_idx(d: &TokenTrait, k: Vec<u8>) -> TraitValueKey {
+    (
+        d.trait_type.as_bytes().to_vec(),
+        d.value.as_bytes().to_vec(),
+        k,
+    )
+}
+
+/// Token attributes keyed by (token_id, trait_type, value), indexed by (trait_type, value)
+pub fn token_traits<'a>(
+) -> IndexedMap<'a, (&'a str, &'a str, &'a str), TokenTrait, TokenTraitIndexes<'a>> {
+    let indexes = TokenTraitIndexes {
+        trait_value: MultiIndex::new(token_trait_value_idx, "token_traits", "token_traits__value"),
+    };
+    IndexedMap::new("token_traits", indexes)
+}
+
+/// Progress of the reindex of tokens minted before 0.10.0, missing once done
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema, Default)]
+pub struct ReindexProgress {
+    /// Last token reindexed, where the next page starts
+    pub last_token_id: Option<String>,
+    /// Tokens counted so far, saved as the token count once done
+    pub token_count: u64,
+}
+
+pub const REINDEX: Item<ReindexProgress> = Item::new("reindex");
+
+/// Number of tokens having each (trait_type, value)
+pub const TRAIT_COUNTS: Map<(&str, &str), u64> = Map::new("trait_counts");
+
+/// Rarity score of each token, computed by RecomputeRarity
+pub const RARITY_SCORES: Map<&str, u64> = Map::new("rarity_scores");
+/// Rarity ranking keyed by (score, token_id)
+pub const RARITY_RANKING: Map<(U64Key, &str), RarityResponse> = Map::new("rarity_ranking");
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct Stake {
+    pub owner: Addr,
+    pub since: Timestamp,
+}
+
+pub struct StakeIndexes<'a> {
+    pub owner: MultiIndex<'a, (Addr, Vec<u8>), Stake>,
+}
+
+impl<'a> IndexList<Stake> for StakeIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<Stake>> + '_> {
+        let v: Vec<&dyn Index<Stake>> = vec![&self.owner];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn stake_owner_idx(d: &Stake, k: Vec<u8>) -> (Addr, Vec<u8>) {
+    (d.owner.clone(), k)
+}
+
+/// Current stakes keyed by token_id, indexed by owner
+pub fn stakes<'a>() -> IndexedMap<'a, &'a str, Stake, StakeIndexes<'a>> {
+    let indexes = StakeIndexes {
+        owner: MultiIndex::new(stake_owner_idx, "stakes", "stakes__owner"),
+    };
+    IndexedMap::new("stakes", indexes)
+}
+
+/// Seconds staked by each token over its finished stakes
+pub const STAKED_SECONDS: Map<&str, u64> = Map::new("staked_seconds");
+
+/// Fusion recipes keyed by recipe_id
+pub const RECIPES: Map<&str, Recipe> = Map::new("recipes");
+/// Number of fusions, used to derive the id of fused tokens
+pub const FUSION_COUNT: Item<u64> = Item::new("fusion_count");
+
+/// Lineage of bred tokens
+pub const LINEAGE: Map<&str, Lineage> = Map::new("lineage");
+/// End of the breeding cooldown of each parent
+pub const BREED_COOLDOWNS: Map<&str, Expiration> = Map::new("breed_cooldowns");
+/// Number of bred tokens, used to derive the id of children
+pub const BREED_COUNT: Item<u64> = Item::new("breed_count");
+
+/// Evolution table keyed by the name of the stage evolved from
+pub const EVOLUTIONS: Map<&str, Evolution> = Map::new("evolutions");
+
+/// Cw721 token equipped to a parent token, from this contract or an external one
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct EquippedNft {
+    pub parent: String,
+    pub contract: Addr,
+    pub token_id: String,
+}
+
+pub struct EquippedNftIndexes<'a> {
+    pub parent: MultiIndex<'a, (String, Vec<u8>), EquippedNft>,
+}
+
+impl<'a> IndexList<EquippedNft> for EquippedNftIndexes<'a> {
+    fn get_indexes(&'_ self) -> Box<dyn Iterator<Item = &'_ dyn Index<EquippedNft>> + '_> {
+        let v: Vec<&dyn Index<EquippedNft>> = vec![&self.parent];
+        Box::new(v.into_iter())
+    }
+}
+
+pub fn equipped_nft_parent_idx(d: &EquippedNft, k: Vec<u8>) -> (String, Vec<u8>) {
+    (d.parent.clone(), k)
+}
+
+/// Equipped tokens keyed by (contract, token_id), indexed by parent
+pub fn equipped_nfts<'a>(
+) -> IndexedMap<'a, (&'a Addr, &'a str), EquippedNft, EquippedNftIndexes<'a>> {
+    let indexes = EquippedNftIndexes {
+        parent: MultiIndex::new(
+            equipped_nft_parent_idx,
+            "equipped_nfts",
+            "equipped_nfts__parent",
+        ),
+    };
+    IndexedMap::new("equipped_nfts", indexes)
+}
+
+/// Cw20 balances equipped to each token keyed by (parent, contract)
+pub const EQUIPPED_CW20: Map<(&str, &Addr), Uint128> = Map::new("equipped_cw20");
+
+/// Contracts whose tokens or cw20 balances can be equipped
+pub const CHILD_CONTRACTS: Map<&Addr, Empty> = Map::new("child_contracts");
+
+/// Collections whose tokens can be exchanged
+pub const EXCHANGE_SOURCES: Map<&Addr, ExchangeSource> = Map::new("exchange_sources");
+/// Exchanged tokens keyed by (source contract, source token_id)
+pub const EXCHANGES: Map<(&Addr, &str), ExchangeRecord> = Map::new("exchanges");
+
+/// Vaults of fractionalized tokens
+pub const VAULTS: Map<&str, Vault> = Map::new("vaults");
+/// Share balances keyed by (token_id, holder)
+pub const SHARES: Map<(&str, &Addr), Uint128> = Map::new("shares");
@@ -1,14 +1,39 @@
 #[cfg(test)]
 mod tests {
-    use crate::contract::{execute, instantiate, query};
+    use crate::contract::{execute, instantiate, migrate, query};
     use crate::error::ContractError;
+    use crate::migrate::{CONTRACT_NAME, CONTRACT_VERSION, LEGACY_CONTRACT_NAME};
+    use crate::state::{MigrateConfig, CONFIG, HELD_SINCE, SALES, SALES_STATS, TRAIT_COUNTS};
 
-    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
-    use cosmwasm_std::{from_binary, Binary};
-    use cw721::{Cw721Contract, Cw721Query, NftInfoResponse};
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info, MOCK_CONTRACT_ADDR};
+    use cosmwasm_std::{
+        attr, coin, coins, from_binary, to_binary, Addr, BankMsg, CosmosMsg, Event, Storage,
+        SubMsg, Uint128, WasmMsg,
+    };
+    use cw0::{Duration, Expiration};
+    use cw2::{get_contract_version, set_contract_version};
+    use cw20::{Cw20ExecuteMsg, Cw20ReceiveMsg};
+    use cw721::{
+        AllNftInfoResponse, ContractInfoResponse, Cw721ExecuteMsg, Cw721Query, Cw721ReceiveMsg,
+        NftInfoResponse, TokensResponse,
+    };
+    use cw721_base::state::TokenInfo;
     use cw721_base::MintMsg;
-    use rest_nft::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-    use rest_nft::state::{Extension, Metadata, RestNFTContract, Trait};
+    use rest_nft::msg::{
+        AllNftInfoBatchResponse, EquippedResponse, EvolutionsResponse, ExchangeSourcesResponse,
+        ExchangedResponse, ExecuteMsg, ExperienceResponse, InstantiateMsg, LineageResponse,
+        MetadataHistoryResponse, MigrateMsg, NftInfoRangeResponse, PauseStatusResponse, QueryMsg,
+        RarityRankingResponse, RarityResponse, ReceiveMsg, RecipesResponse, RentalListingsResponse,
+        RentalResponse, SalesHistoryResponse, SalesStatsResponse, ShareBalance,
+        ShareHoldersResponse, SharesResponse, StakeInfoResponse, TokensWithInfoResponse,
+        TraitCount, TraitCountsResponse, TransferLockResponse, TransferableResponse,
+        UserOfResponse,
+    };
+    use rest_nft::state::{
+        Child, CollectionInfo, Evolution, ExchangeRecord, ExchangeSource, Extension, FieldPatch,
+        LevelInheritance, Metadata, MetadataPatch, Operation, Recipe, RestNFTContract, Trait,
+        TraitPatch,
+    };
 
     const CREATOR: &str = "creator";
     const PUBLIC: &str = "public";
@@ -26,6 +51,7 @@ mod tests {
             token_supply: None,
             buy_amount: 0,
             available: false,
+            collection_info: None,
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
@@ -62,6 +88,7 @@ mod tests {
             token_supply: Some(1),
             buy_amount: 0,
             available: false,
+            collection_info: None,
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
@@ -99,6 +126,7 @@ mod tests {
             token_supply: Some(1),
             buy_amount: 0,
             available: false,
+            collection_info: None,
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
@@ -129,9 +157,8 @@ mod tests {
         assert_eq!(token_count, 0);
 
         let res = RestNFTContract::default().nft_info(deps.as_ref(), token_id.into());
-        match res {
-            Ok(_) => panic!("Should not return token info"),
-            Err(_) => {}
+        if res.is_ok() {
+            panic!("Should not return token info")
         }
     }
 
@@ -147,6 +174,7 @@ mod tests {
             token_supply: Some(1),
             buy_amount: 0,
             available: false,
+            collection_info: None,
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
@@ -174,9 +202,6 @@ mod tests {
             token_uri: Some("https://moon.com".to_string()),
             extension: Some(Metadata {
                 image: None,
-                level: None,
-                rarity: None,
-                role: None,
                 image_data: None,
                 external_url: None,
                 description: None,
@@ -205,9 +230,6 @@ mod tests {
             token_uri: Some("https://moonit.com".to_string()),
             extension: Some(Metadata {
                 image: None,
-                level: None,
-                rarity: None,
-                role: None,
                 image_data: None,
                 external_url: None,
                 description: None,
@@ -235,6 +257,7 @@ mod tests {
             token_supply: Some(1),
             buy_amount: 0,
             available: false,
+            collection_info: None,
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
@@ -271,9 +294,6 @@ mod tests {
             token_uri: Some("https://moonit.com".to_string()),
             extension: Some(Metadata {
                 image: None,
-                level: None,
-                rarity: None,
-                role: None,
                 image_data: None,
                 external_url: None,
                 description: None,
@@ -305,6 +325,7 @@ mod tests {
             token_supply: Some(1),
             buy_amount: 0,
             available: false,
+            collection_info: None,
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
@@ -338,9 +359,6 @@ mod tests {
             token_uri: Some("https://moonit.com".to_string()),
             extension: Some(Metadata {
                 image: None,
-                level: None,
-                rarity: None,
-                role: None,
                 image_data: None,
                 external_url: None,
                 description: None,
@@ -389,6 +407,7 @@ mod tests {
             token_supply: Some(1),
             buy_amount: 0,
             available: false,
+            collection_info: None,
         };
 
         instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
@@ -400,43 +419,3702 @@ mod tests {
             token_uri: None,
             extension: Extension::Some(Metadata {
                 image: Some("image1".to_string()),
-                level: Some("1".to_string()),
-                rarity: Some("100".to_string()),
-                role: Some("undefined".to_string()),
                 image_data: Some("ipfs".to_string()),
                 external_url: Some("ipfs".to_string()),
                 description: Some("Bee collecting honey".to_string()),
                 name: Some("Bumble bee".to_string()),
-                attributes: Some(vec![Trait {
-                    display_type: None,
-                    trait_type: "color".to_string(),
-                    value: "yellow".to_string(),
-                }]),
+                attributes: Some(vec![
+                    Trait {
+                        display_type: None,
+                        trait_type: "color".to_string(),
+                        value: "yellow".to_string(),
+                    },
+                    Trait {
+                        display_type: None,
+                        trait_type: "level".to_string(),
+                        value: "1".to_string(),
+                    },
+                ]),
                 background_color: Some("yellow".to_string()),
                 animation_url: None,
                 youtube_url: None,
             }),
         });
         // Mint a token
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), mint_msg).unwrap();
+        execute(deps.as_mut(), mock_env(), info.clone(), mint_msg).unwrap();
 
         // Create token
         let level_msg = ExecuteMsg::SetLevel {
             token_id: "001".to_string(),
             level: "55".to_string(),
         };
-        let res = execute(deps.as_mut(), mock_env(), info.clone(), level_msg).unwrap();
+        execute(deps.as_mut(), mock_env(), info.clone(), level_msg).unwrap();
 
-        let load_token = QueryMsg::NftInfo {
-            token_id: "001".to_string(),
-        };
         let cw721_contract = RestNFTContract::default();
         let data = cw721_contract
             .tokens
-            .load(deps.as_mut().storage, &"001".to_string())
+            .load(deps.as_mut().storage, "001")
             .unwrap();
         // Assert the level was successfully updated
-        assert_eq!(data.extension.unwrap().level, Some("55".to_string()));
+        let level = data
+            .extension
+            .unwrap()
+            .attributes
+            .unwrap()
+            .into_iter()
+            .find(|t| t.trait_type == "level")
+            .unwrap();
+        assert_eq!(level.value, "55".to_string());
         //println!("{:?}", data);
     }
+
+    #[test]
+    fn collection_info() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: Some(CollectionInfo {
+                description: Some("Ships of the REST-verse".to_string()),
+                image: Some("ipfs://ships.png".to_string()),
+                external_link: None,
+                banner_image: None,
+                contract_uri: Some("ipfs://ships.json".to_string()),
+            }),
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let res: CollectionInfo =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::CollectionInfo {}).unwrap())
+                .unwrap();
+        assert_eq!(Some("ipfs://ships.json".to_string()), res.contract_uri);
+
+        let new_info = CollectionInfo {
+            description: Some("Ships of the REST-verse".to_string()),
+            image: Some("ipfs://ships.png".to_string()),
+            external_link: Some("https://redeyedspacetoads.io".to_string()),
+            banner_image: Some("ipfs://banner.png".to_string()),
+            contract_uri: Some("ipfs://ships-v2.json".to_string()),
+        };
+
+        // Public cannot set collection info
+        let exec_msg = ExecuteMsg::SetCollectionInfo {
+            name: None,
+            symbol: None,
+            collection_info: new_info.clone(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+
+        // Name and symbol follow the instantiate rules
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::SetCollectionInfo {
+                name: Some("S".to_string()),
+                symbol: None,
+                collection_info: new_info.clone(),
+            },
+        );
+        assert_eq!(
+            ContractError::InvalidName { min: 3, max: 50 },
+            res.unwrap_err()
+        );
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::SetCollectionInfo {
+                name: None,
+                symbol: Some("SPACE 2".to_string()),
+                collection_info: new_info.clone(),
+            },
+        );
+        assert_eq!(
+            ContractError::InvalidSymbol { min: 3, max: 12 },
+            res.unwrap_err()
+        );
+
+        // Minter can update collection info and contract name
+        let exec_msg = ExecuteMsg::SetCollectionInfo {
+            name: Some("StarShips".to_string()),
+            symbol: None,
+            collection_info: new_info.clone(),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg.clone()).unwrap();
+
+        let res: CollectionInfo =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::CollectionInfo {}).unwrap())
+                .unwrap();
+        assert_eq!(new_info, res);
+
+        let res: ContractInfoResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::ContractInfo {}).unwrap())
+                .unwrap();
+        assert_eq!("StarShips".to_string(), res.name);
+        assert_eq!("SPACE".to_string(), res.symbol);
+
+        // Cannot update after freeze
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Freeze {},
+        )
+        .unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(ContractError::ContractFrozen {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn base_uri() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for (token_id, token_uri) in [("1", None), ("2", Some("https://moon.com".to_string()))] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri,
+                extension: None,
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        let exec_msg = ExecuteMsg::SetBaseUri {
+            base_uri: Some("ipfs://cid1/".to_string()),
+            suffix: Some(".json".to_string()),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        // Tokens without a uri derive it from the base uri
+        let res: NftInfoResponse<Extension> = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::NftInfo {
+                    token_id: "1".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Some("ipfs://cid1/1.json".to_string()), res.token_uri);
+
+        // Explicit uri takes precedence
+        let res: AllNftInfoResponse<Extension> = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::AllNftInfo {
+                    token_id: "2".to_string(),
+                    include_expired: None,
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Some("https://moon.com".to_string()), res.info.token_uri);
+        assert_eq!(OWNER.to_string(), res.access.owner);
+
+        // Migrate the whole collection to a new cid
+        let exec_msg = ExecuteMsg::SetBaseUri {
+            base_uri: Some("ipfs://cid2/".to_string()),
+            suffix: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg.clone()).unwrap();
+        let res: NftInfoResponse<Extension> = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::NftInfo {
+                    token_id: "1".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(Some("ipfs://cid2/1".to_string()), res.token_uri);
+
+        // Cannot change base uri after freeze
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Freeze {},
+        )
+        .unwrap();
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(ContractError::ContractFrozen {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn metadata_history() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let metadata = Metadata {
+            name: Some("Toad".to_string()),
+            attributes: Some(vec![Trait {
+                display_type: None,
+                trait_type: "level".to_string(),
+                value: "1".to_string(),
+            }]),
+            ..Metadata::default()
+        };
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: Some(metadata.clone()),
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Update {
+            token_id: "1".to_string(),
+            token_uri: Some("https://moon.com".to_string()),
+            extension: Some(metadata.clone()),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let mut env = mock_env();
+        env.block.height += 1;
+        let exec_msg = ExecuteMsg::SetLevel {
+            token_id: "1".to_string(),
+            level: "2".to_string(),
+        };
+        execute(deps.as_mut(), env.clone(), info.clone(), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::MetadataHistory {
+            token_id: "1".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: MetadataHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(2, res.changes.len());
+        assert_eq!(1, res.changes[0].version);
+        assert_eq!(None, res.changes[0].old_token_uri);
+        assert_eq!(
+            Some("https://moon.com".to_string()),
+            res.changes[0].new_token_uri
+        );
+        assert_eq!(2, res.changes[1].version);
+        assert_eq!(env.block.height, res.changes[1].height);
+        assert_eq!(CREATOR, res.changes[1].editor.as_str());
+        assert_eq!(Some(metadata.clone()), res.changes[1].old_extension);
+        let level = res.changes[1]
+            .new_extension
+            .clone()
+            .unwrap()
+            .attributes
+            .unwrap()[0]
+            .clone();
+        assert_eq!("2".to_string(), level.value);
+
+        // Paginate after the first version
+        let query_msg = QueryMsg::MetadataHistory {
+            token_id: "1".to_string(),
+            start_after: Some(1),
+            limit: Some(1),
+        };
+        let res: MetadataHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(1, res.changes.len());
+        assert_eq!(2, res.changes[0].version);
+
+        // Only minter can set retention
+        let exec_msg = ExecuteMsg::SetHistoryRetention { retention: Some(2) };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        // Oldest entry is pruned beyond retention
+        let exec_msg = ExecuteMsg::SetLevel {
+            token_id: "1".to_string(),
+            level: "3".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::MetadataHistory {
+            token_id: "1".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: MetadataHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+        let versions: Vec<u64> = res.changes.iter().map(|c| c.version).collect();
+        assert_eq!(vec![2, 3], versions);
+
+        // The history is append-only, a token minted again after a burn
+        // continues it
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: Some(metadata.clone()),
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let res: MetadataHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+        let versions: Vec<u64> = res.changes.iter().map(|c| c.version).collect();
+        assert_eq!(vec![2, 3], versions);
+
+        let exec_msg = ExecuteMsg::Update {
+            token_id: "1".to_string(),
+            token_uri: None,
+            extension: Some(metadata),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        assert!(res.attributes.contains(&attr("version", "4")));
+    }
+
+    #[test]
+    fn patch_metadata() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: Some("https://moon.com".to_string()),
+            extension: Some(Metadata {
+                name: Some("Toad".to_string()),
+                description: Some("A red eyed toad".to_string()),
+                attributes: Some(vec![
+                    Trait {
+                        display_type: None,
+                        trait_type: "color".to_string(),
+                        value: "red".to_string(),
+                    },
+                    Trait {
+                        display_type: None,
+                        trait_type: "level".to_string(),
+                        value: "1".to_string(),
+                    },
+                ]),
+                ..Metadata::default()
+            }),
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let patch = MetadataPatch {
+            name: Some(FieldPatch::Set("Big Toad".to_string())),
+            description: Some(FieldPatch::Clear),
+            attributes: Some(vec![
+                TraitPatch {
+                    trait_type: "level".to_string(),
+                    value: FieldPatch::Set("2".to_string()),
+                    display_type: Some("number".to_string()),
+                },
+                TraitPatch {
+                    trait_type: "color".to_string(),
+                    value: FieldPatch::Clear,
+                    display_type: None,
+                },
+                TraitPatch {
+                    trait_type: "role".to_string(),
+                    value: FieldPatch::Set("scout".to_string()),
+                    display_type: None,
+                },
+            ]),
+            ..MetadataPatch::default()
+        };
+
+        // Public cannot patch metadata
+        let exec_msg = ExecuteMsg::PatchMetadata {
+            token_id: "1".to_string(),
+            patch: patch.clone(),
+            expected_version: Some(0),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg.clone()).unwrap();
+
+        let res = RestNFTContract::default()
+            .nft_info(deps.as_ref(), "1".into())
+            .unwrap();
+        // Fields left out of the patch are unchanged
+        assert_eq!(Some("https://moon.com".to_string()), res.token_uri);
+        let metadata = res.extension.unwrap();
+        assert_eq!(Some("Big Toad".to_string()), metadata.name);
+        assert_eq!(None, metadata.description);
+        assert_eq!(
+            vec![
+                Trait {
+                    display_type: Some("number".to_string()),
+                    trait_type: "level".to_string(),
+                    value: "2".to_string(),
+                },
+                Trait {
+                    display_type: None,
+                    trait_type: "role".to_string(),
+                    value: "scout".to_string(),
+                },
+            ],
+            metadata.attributes.unwrap()
+        );
+
+        // Stale version is rejected
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg);
+        assert_eq!(
+            ContractError::VersionMismatch {
+                expected: 0,
+                current: 1
+            },
+            res.unwrap_err()
+        );
+
+        // Unknown token
+        let exec_msg = ExecuteMsg::PatchMetadata {
+            token_id: "2".to_string(),
+            patch,
+            expected_version: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg);
+        assert_eq!(ContractError::TokenNotFound {}, res.unwrap_err());
+
+        // A token without metadata keeps none unless a field is set
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "2".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::PatchMetadata {
+            token_id: "2".to_string(),
+            patch: MetadataPatch {
+                token_uri: Some(FieldPatch::Set("https://moon.com".to_string())),
+                description: Some(FieldPatch::Clear),
+                attributes: Some(vec![TraitPatch {
+                    trait_type: "color".to_string(),
+                    value: FieldPatch::Clear,
+                    display_type: None,
+                }]),
+                ..MetadataPatch::default()
+            },
+            expected_version: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let res = RestNFTContract::default()
+            .nft_info(deps.as_ref(), "2".into())
+            .unwrap();
+        assert_eq!(Some("https://moon.com".to_string()), res.token_uri);
+        assert_eq!(None, res.extension);
+
+        let exec_msg = ExecuteMsg::PatchMetadata {
+            token_id: "2".to_string(),
+            patch: MetadataPatch {
+                name: Some(FieldPatch::Set("Toad".to_string())),
+                ..MetadataPatch::default()
+            },
+            expected_version: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let res = RestNFTContract::default()
+            .nft_info(deps.as_ref(), "2".into())
+            .unwrap();
+        assert_eq!(
+            Some(Metadata {
+                name: Some("Toad".to_string()),
+                ..Metadata::default()
+            }),
+            res.extension
+        );
+    }
+
+    #[test]
+    fn experience() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        // Cannot add experience before progression is set
+        let game = mock_info("game", &[]);
+        let exec_msg = ExecuteMsg::AddExperience {
+            token_id: "1".to_string(),
+            amount: 150,
+        };
+        let res = execute(deps.as_mut(), mock_env(), game.clone(), exec_msg.clone());
+        assert_eq!(ContractError::ProgressionNotSet {}, res.unwrap_err());
+
+        // Thresholds must be ascending
+        let set_msg = ExecuteMsg::SetProgression {
+            game_server: "game".to_string(),
+            level_thresholds: vec![0, 250, 100],
+            max_level: 3,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), set_msg);
+        assert_eq!(ContractError::InvalidProgression {}, res.unwrap_err());
+
+        let set_msg = ExecuteMsg::SetProgression {
+            game_server: "game".to_string(),
+            level_thresholds: vec![0, 100, 250, 500],
+            max_level: 3,
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            set_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), set_msg).unwrap();
+
+        // Only the game server can add experience
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+
+        let res = execute(deps.as_mut(), mock_env(), game.clone(), exec_msg).unwrap();
+        assert_eq!(2, res.events.len());
+        assert_eq!(
+            Event::new("rest_nft.level")
+                .add_attribute("version", "1")
+                .add_attribute("token_id", "1")
+                .add_attribute("old_level", "")
+                .add_attribute("new_level", "2"),
+            res.events[1]
+        );
+
+        let query_msg = QueryMsg::Experience {
+            token_id: "1".to_string(),
+        };
+        let res: ExperienceResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+        assert_eq!(150, res.experience);
+        assert_eq!(Some(2), res.level);
+
+        // Level trait is recomputed
+        let token = RestNFTContract::default()
+            .nft_info(deps.as_ref(), "1".into())
+            .unwrap();
+        let level = token.extension.unwrap().attributes.unwrap()[0].clone();
+        assert_eq!("level".to_string(), level.trait_type);
+        assert_eq!("2".to_string(), level.value);
+
+        // Level is capped at max_level
+        let exec_msg = ExecuteMsg::AddExperience {
+            token_id: "1".to_string(),
+            amount: 1000,
+        };
+        execute(deps.as_mut(), mock_env(), game.clone(), exec_msg.clone()).unwrap();
+        let res: ExperienceResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+        assert_eq!(1150, res.experience);
+        assert_eq!(Some(3), res.level);
+
+        // No level up event once capped
+        let res = execute(deps.as_mut(), mock_env(), game, exec_msg).unwrap();
+        assert!(res.events.is_empty());
+
+        // SetLevel is bounded by max_level once progression is set
+        let exec_msg = ExecuteMsg::SetLevel {
+            token_id: "1".to_string(),
+            level: "9".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg);
+        assert_eq!(
+            ContractError::InvalidLevel { max_level: 3 },
+            res.unwrap_err()
+        );
+
+        // Levels set by Mint or SetLevel are kept by the next AddExperience
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "2".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                attributes: Some(vec![Trait {
+                    display_type: None,
+                    trait_type: "level".to_string(),
+                    value: "3".to_string(),
+                }]),
+                ..Metadata::default()
+            }),
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let add_msg = ExecuteMsg::AddExperience {
+            token_id: "2".to_string(),
+            amount: 1,
+        };
+        let game = mock_info("game", &[]);
+        let res = execute(deps.as_mut(), mock_env(), game.clone(), add_msg.clone()).unwrap();
+        assert!(res.events.is_empty());
+        assert!(res.attributes.contains(&attr("experience", "251")));
+
+        let exec_msg = ExecuteMsg::SetLevel {
+            token_id: "2".to_string(),
+            level: "2".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), game, add_msg).unwrap();
+        assert!(res.events.is_empty());
+        assert!(res.attributes.contains(&attr("level", "2")));
+        assert!(res.attributes.contains(&attr("experience", "101")));
+
+        // Experience does not carry over to a token minted again after a burn
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let res: ExperienceResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(0, res.experience);
+    }
+
+    #[test]
+    fn tokens_by_trait() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let toad = |level: &str| {
+            Some(Metadata {
+                attributes: Some(vec![
+                    Trait {
+                        display_type: None,
+                        trait_type: "color".to_string(),
+                        value: "red".to_string(),
+                    },
+                    Trait {
+                        display_type: None,
+                        trait_type: "level".to_string(),
+                        value: level.to_string(),
+                    },
+                ]),
+                ..Metadata::default()
+            })
+        };
+        for (token_id, level) in [("1", "1"), ("2", "1"), ("3", "2")] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: toad(level),
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        let tokens_by_trait = |deps: &cosmwasm_std::OwnedDeps<_, _, _>,
+                               value: &str,
+                               start_after: Option<String>|
+         -> Vec<String> {
+            let query_msg = QueryMsg::TokensByTrait {
+                trait_type: "level".to_string(),
+                value: value.to_string(),
+                start_after,
+                limit: None,
+            };
+            let res: TokensResponse =
+                from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+            res.tokens
+        };
+        assert_eq!(vec!["1", "2"], tokens_by_trait(&deps, "1", None));
+        assert_eq!(
+            vec!["2"],
+            tokens_by_trait(&deps, "1", Some("1".to_string()))
+        );
+
+        // SetLevel moves the token to the new value
+        let exec_msg = ExecuteMsg::SetLevel {
+            token_id: "1".to_string(),
+            level: "2".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        assert_eq!(vec!["2"], tokens_by_trait(&deps, "1", None));
+        assert_eq!(vec!["1", "3"], tokens_by_trait(&deps, "2", None));
+
+        // Update removes dropped traits
+        let exec_msg = ExecuteMsg::Update {
+            token_id: "2".to_string(),
+            token_uri: None,
+            extension: None,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        assert!(tokens_by_trait(&deps, "1", None).is_empty());
+
+        // Burn removes the token from the index
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "3".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), exec_msg).unwrap();
+        assert_eq!(vec!["1"], tokens_by_trait(&deps, "2", None));
+
+        let query_msg = QueryMsg::TraitCounts {
+            trait_type: "color".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: TraitCountsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![TraitCount {
+                trait_type: "color".to_string(),
+                value: "red".to_string(),
+                count: 1,
+            }],
+            res.counts
+        );
+
+        let query_msg = QueryMsg::TraitCounts {
+            trait_type: "level".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: TraitCountsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(1, res.counts.len());
+        assert_eq!("2".to_string(), res.counts[0].value);
+    }
+
+    #[test]
+    fn rarity() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        // color red is common, gold is unique
+        for (token_id, color) in [("1", "red"), ("2", "red"), ("3", "red"), ("4", "gold")] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: Some(Metadata {
+                    attributes: Some(vec![Trait {
+                        display_type: None,
+                        trait_type: "color".to_string(),
+                        value: color.to_string(),
+                    }]),
+                    ..Metadata::default()
+                }),
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        // Rarity is only computed once frozen
+        let exec_msg = ExecuteMsg::RecomputeRarity {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg.clone());
+        assert_eq!(ContractError::ContractNotFrozen {}, res.unwrap_err());
+
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Freeze {},
+        )
+        .unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+
+        // Recompute in two pages
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let last_token_id = res
+            .attributes
+            .iter()
+            .find(|attr| attr.key == "last_token_id")
+            .unwrap()
+            .value
+            .clone();
+        assert_eq!("2", last_token_id);
+        assert_eq!(
+            vec![Event::new("rest_nft.recompute_rarity")
+                .add_attribute("version", "1")
+                .add_attribute("token_count", "4")
+                .add_attribute("last_token_id", "2")],
+            res.events
+        );
+        let exec_msg = ExecuteMsg::RecomputeRarity {
+            start_after: Some(last_token_id),
+            limit: Some(2),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let query_msg = QueryMsg::Rarity {
+            token_id: "4".to_string(),
+        };
+        let res: RarityResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(4000, res.score);
+
+        let query_msg = QueryMsg::RarityRanking {
+            start_after: None,
+            limit: Some(2),
+        };
+        let res: RarityRankingResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![
+                RarityResponse {
+                    token_id: "4".to_string(),
+                    score: 4000,
+                },
+                RarityResponse {
+                    token_id: "3".to_string(),
+                    score: 1333,
+                },
+            ],
+            res.tokens
+        );
+
+        let query_msg = QueryMsg::RarityRanking {
+            start_after: Some("3".to_string()),
+            limit: None,
+        };
+        let res: RarityRankingResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        let tokens: Vec<String> = res.tokens.into_iter().map(|r| r.token_id).collect();
+        assert_eq!(vec!["2", "1"], tokens);
+
+        // burned tokens leave the ranking
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "4".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), exec_msg).unwrap();
+        let query_msg = QueryMsg::RarityRanking {
+            start_after: None,
+            limit: None,
+        };
+        let res: RarityRankingResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        let tokens: Vec<String> = res.tokens.into_iter().map(|r| r.token_id).collect();
+        assert_eq!(vec!["3", "2", "1"], tokens);
+        let query_msg = QueryMsg::Rarity {
+            token_id: "4".to_string(),
+        };
+        assert!(query(deps.as_ref(), mock_env(), query_msg).is_err());
+    }
+
+    #[test]
+    fn all_tokens() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for token_id in ["Defiant", "Enterprise", "Voyager", "Excelsior"] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: None,
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        let all_tokens = |deps: &cosmwasm_std::OwnedDeps<_, _, _>,
+                          start_after: Option<&str>,
+                          end_before: Option<&str>,
+                          reverse: bool|
+         -> Vec<String> {
+            let query_msg = QueryMsg::AllTokens {
+                start_after: start_after.map(String::from),
+                end_before: end_before.map(String::from),
+                limit: Some(2),
+                reverse: Some(reverse),
+            };
+            let res: TokensResponse =
+                from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+            res.tokens
+        };
+        assert_eq!(
+            vec!["Defiant", "Enterprise"],
+            all_tokens(&deps, None, None, false)
+        );
+        assert_eq!(
+            vec!["Excelsior", "Voyager"],
+            all_tokens(&deps, Some("Enterprise"), None, false)
+        );
+        assert_eq!(
+            vec!["Voyager", "Excelsior"],
+            all_tokens(&deps, None, None, true)
+        );
+        assert_eq!(
+            vec!["Enterprise", "Defiant"],
+            all_tokens(&deps, None, Some("Excelsior"), true)
+        );
+        assert_eq!(
+            vec!["Enterprise"],
+            all_tokens(&deps, Some("Defiant"), Some("Excelsior"), false)
+        );
+
+        let exec_msg = ExecuteMsg::SetBaseUri {
+            base_uri: Some("ipfs://cid/".to_string()),
+            suffix: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let query_msg = QueryMsg::NftInfoRange {
+            start_after: Some("Enterprise".to_string()),
+            limit: None,
+        };
+        let res: NftInfoRangeResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(2, res.tokens.len());
+        assert_eq!("Excelsior", res.tokens[0].token_id);
+        assert_eq!(
+            Some("ipfs://cid/Excelsior".to_string()),
+            res.tokens[0].info.token_uri
+        );
+
+        let query_msg = QueryMsg::AllNftInfoBatch {
+            token_ids: vec!["Voyager".to_string(), "Defiant".to_string()],
+            include_expired: None,
+        };
+        let res: AllNftInfoBatchResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!("Voyager", res.tokens[0].token_id);
+        assert_eq!(OWNER, res.tokens[0].info.access.owner);
+        assert_eq!(
+            Some("ipfs://cid/Defiant".to_string()),
+            res.tokens[1].info.info.token_uri
+        );
+
+        // Unknown tokens fail the batch
+        let query_msg = QueryMsg::AllNftInfoBatch {
+            token_ids: vec!["Voyager".to_string(), "Reliant".to_string()],
+            include_expired: None,
+        };
+        query(deps.as_ref(), mock_env(), query_msg).unwrap_err();
+    }
+
+    #[test]
+    fn tokens_with_info() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for (token_id, owner) in [("1", OWNER), ("2", PUBLIC), ("3", OWNER)] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: owner.to_string(),
+                token_uri: Some(format!("https://moon.com/{}", token_id)),
+                extension: Some(Metadata {
+                    name: Some(format!("Toad #{}", token_id)),
+                    ..Metadata::default()
+                }),
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        let exec_msg = ExecuteMsg::Approve {
+            spender: PUBLIC.to_string(),
+            token_id: "3".to_string(),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::TokensWithInfo {
+            owner: OWNER.to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: TokensWithInfoResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(2, res.tokens.len());
+        assert_eq!("1", res.tokens[0].token_id);
+        assert_eq!(
+            Some("https://moon.com/1".to_string()),
+            res.tokens[0].token_uri
+        );
+        assert_eq!(
+            Some("Toad #1".to_string()),
+            res.tokens[0].extension.clone().unwrap().name
+        );
+        assert!(res.tokens[0].approvals.is_empty());
+        assert_eq!("3", res.tokens[1].token_id);
+        assert_eq!(PUBLIC, res.tokens[1].approvals[0].spender);
+
+        let query_msg = QueryMsg::TokensWithInfo {
+            owner: OWNER.to_string(),
+            start_after: Some("1".to_string()),
+            limit: Some(1),
+        };
+        let res: TokensWithInfoResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(1, res.tokens.len());
+        assert_eq!("3", res.tokens[0].token_id);
+    }
+
+    #[test]
+    fn buy() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: Some(2),
+            buy_amount: 100,
+            available: true,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for token_id in ["1", "2"] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: CREATOR.to_string(),
+                token_uri: None,
+                extension: None,
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+        let exec_msg = ExecuteMsg::Approve {
+            spender: "spender".to_string(),
+            token_id: "1".to_string(),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        // The token is handed over directly, also to a recipient other than
+        // the buyer, and only the payment to the minter is sent
+        let exec_msg = ExecuteMsg::Buy {
+            recipient: OWNER.to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            exec_msg,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: CREATOR.to_string(),
+                amount: coins(100, "uusd"),
+            })],
+            res.messages
+        );
+
+        // Approvals granted by the minter do not survive the sale
+        let contract = RestNFTContract::default();
+        let res = contract
+            .owner_of(deps.as_ref(), mock_env(), "1".to_string(), true)
+            .unwrap();
+        assert_eq!(OWNER, res.owner);
+        assert!(res.approvals.is_empty());
+
+        // Selling a minted token does not change the token count
+        assert_eq!(2, contract.num_tokens(deps.as_ref()).unwrap().count);
+    }
+
+    #[test]
+    fn sales_history() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: Some(4),
+            buy_amount: 100,
+            available: true,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for token_id in ["1", "2", "3"] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: CREATOR.to_string(),
+                token_uri: None,
+                extension: None,
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        // Wrong price is rejected
+        let exec_msg = ExecuteMsg::Buy {
+            recipient: OWNER.to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(99, "uusd")),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Funds {}, res.unwrap_err());
+
+        // Buyer can send the token to someone else
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            exec_msg,
+        )
+        .unwrap();
+        for buyer in ["alice", "bob"] {
+            let exec_msg = ExecuteMsg::Buy {
+                recipient: buyer.to_string(),
+            };
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(buyer, &coins(100, "uusd")),
+                exec_msg,
+            )
+            .unwrap();
+        }
+
+        let owner = RestNFTContract::default()
+            .owner_of(deps.as_ref(), mock_env(), "1".to_string(), false)
+            .unwrap();
+        assert_eq!(OWNER, owner.owner);
+
+        let query_msg = QueryMsg::SalesHistory {
+            start_after: None,
+            limit: None,
+        };
+        let res: SalesHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(3, res.sales.len());
+        assert_eq!(1, res.sales[0].id);
+        assert_eq!("1", res.sales[0].token_id);
+        assert_eq!("alice", res.sales[0].buyer.as_str());
+        assert_eq!(OWNER, res.sales[0].recipient.as_str());
+        assert_eq!(coin(100, "uusd"), res.sales[0].price);
+        assert_eq!(mock_env().block.time, res.sales[0].time);
+
+        let query_msg = QueryMsg::SalesHistory {
+            start_after: Some(2),
+            limit: None,
+        };
+        let res: SalesHistoryResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(1, res.sales.len());
+        assert_eq!("bob", res.sales[0].buyer.as_str());
+
+        let res: SalesStatsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::SalesStats {}).unwrap())
+                .unwrap();
+        assert_eq!(
+            SalesStatsResponse {
+                sold: 3,
+                remaining: Some(1),
+                unique_buyers: 2,
+                revenue: coins(300, "uusd"),
+            },
+            res
+        );
+
+        // Tokens minted without a sale also count against the supply
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "4".to_string(),
+            owner: CREATOR.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let res: SalesStatsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::SalesStats {}).unwrap())
+                .unwrap();
+        assert_eq!(Some(0), res.remaining);
+    }
+
+    #[test]
+    fn events() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 100,
+            available: true,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: CREATOR.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        assert_eq!(
+            vec![Event::new("rest_nft.mint")
+                .add_attribute("version", "1")
+                .add_attribute("token_id", "1")
+                .add_attribute("owner", CREATOR)
+                .add_attribute("minter", CREATOR)],
+            res.events
+        );
+
+        let exec_msg = ExecuteMsg::Buy {
+            recipient: OWNER.to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(100, "uusd")),
+            exec_msg,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                Event::new("rest_nft.buy")
+                    .add_attribute("version", "1")
+                    .add_attribute("sale_id", "1")
+                    .add_attribute("token_id", "1")
+                    .add_attribute("seller", CREATOR)
+                    .add_attribute("buyer", "alice")
+                    .add_attribute("recipient", OWNER)
+                    .add_attribute("price", "100uusd"),
+                Event::new("rest_nft.transfer")
+                    .add_attribute("version", "1")
+                    .add_attribute("token_id", "1")
+                    .add_attribute("old_owner", CREATOR)
+                    .add_attribute("new_owner", OWNER),
+            ],
+            res.events
+        );
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: CREATOR.to_string(),
+                amount: coins(100, "uusd"),
+            }),
+            res.messages[0].msg
+        );
+
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), exec_msg).unwrap();
+        assert_eq!(
+            vec![Event::new("rest_nft.transfer")
+                .add_attribute("version", "1")
+                .add_attribute("token_id", "1")
+                .add_attribute("old_owner", OWNER)
+                .add_attribute("new_owner", PUBLIC)],
+            res.events
+        );
+
+        let exec_msg = ExecuteMsg::Update {
+            token_id: "1".to_string(),
+            token_uri: Some("ipfs://1".to_string()),
+            extension: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        assert_eq!(
+            vec![Event::new("rest_nft.update")
+                .add_attribute("version", "1")
+                .add_attribute("token_id", "1")
+                .add_attribute("editor", CREATOR)
+                .add_attribute("metadata_version", "1")
+                .add_attribute("old_token_uri", "null")
+                .add_attribute("new_token_uri", "\"ipfs://1\"")
+                .add_attribute("old_extension", "null")
+                .add_attribute("new_extension", "null")],
+            res.events
+        );
+
+        let exec_msg = ExecuteMsg::SetLevel {
+            token_id: "1".to_string(),
+            level: "2".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        assert_eq!(2, res.events.len());
+        assert_eq!("rest_nft.update", res.events[0].ty);
+        assert_eq!(
+            Event::new("rest_nft.level")
+                .add_attribute("version", "1")
+                .add_attribute("token_id", "1")
+                .add_attribute("old_level", "")
+                .add_attribute("new_level", "2"),
+            res.events[1]
+        );
+
+        let exec_msg = ExecuteMsg::SetBuyAmount { buy_amount: 200 };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        assert_eq!(
+            vec![Event::new("rest_nft.config")
+                .add_attribute("version", "1")
+                .add_attribute("key", "buy_amount")
+                .add_attribute("old_value", "100")
+                .add_attribute("new_value", "200")],
+            res.events
+        );
+
+        let exec_msg = ExecuteMsg::SetBaseUri {
+            base_uri: Some("ipfs://base/".to_string()),
+            suffix: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        assert_eq!(
+            vec![
+                Event::new("rest_nft.config")
+                    .add_attribute("version", "1")
+                    .add_attribute("key", "base_uri")
+                    .add_attribute("old_value", "null")
+                    .add_attribute("new_value", "\"ipfs://base/\""),
+                Event::new("rest_nft.config")
+                    .add_attribute("version", "1")
+                    .add_attribute("key", "token_uri_suffix")
+                    .add_attribute("old_value", "null")
+                    .add_attribute("new_value", "null"),
+            ],
+            res.events
+        );
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Freeze {},
+        )
+        .unwrap();
+        assert_eq!(
+            vec![Event::new("rest_nft.config")
+                .add_attribute("version", "1")
+                .add_attribute("key", "frozen")
+                .add_attribute("old_value", "false")
+                .add_attribute("new_value", "true")],
+            res.events
+        );
+
+        let exec_msg = ExecuteMsg::SetMinter {
+            minter: "new_minter".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        assert_eq!(
+            vec![Event::new("rest_nft.minter")
+                .add_attribute("version", "1")
+                .add_attribute("old_minter", CREATOR)
+                .add_attribute("new_minter", "new_minter")],
+            res.events
+        );
+
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info(PUBLIC, &[]), exec_msg).unwrap();
+        assert_eq!(
+            vec![Event::new("rest_nft.burn")
+                .add_attribute("version", "1")
+                .add_attribute("token_id", "1")
+                .add_attribute("owner", PUBLIC)
+                .add_attribute("sender", PUBLIC)],
+            res.events
+        );
+    }
+
+    #[test]
+    fn migration() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: Some(10),
+            buy_amount: 100,
+            available: true,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(CONTRACT_NAME, version.contract);
+        assert_eq!(CONTRACT_VERSION, version.version);
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Freeze {},
+        )
+        .unwrap();
+
+        // Contracts instantiated before 0.10.0 carry the cw721-base name with
+        // any version, the old config and sales layouts and tokens without
+        // any index
+        set_contract_version(&mut deps.storage, LEGACY_CONTRACT_NAME, "1.0.0").unwrap();
+        SALES_STATS.remove(&mut deps.storage);
+        deps.storage.set(
+            b"config",
+            br#"{"token_supply":10,"frozen":true,"buy_amount":100,"available":true}"#,
+        );
+        deps.storage.set(b"sales", br#"{"count":2}"#);
+        let contract = RestNFTContract::default();
+        for (token_id, color) in [("1", "red"), ("2", "red"), ("3", "blue")] {
+            let token = TokenInfo {
+                owner: Addr::unchecked(OWNER),
+                approvals: vec![],
+                token_uri: None,
+                extension: Some(Metadata {
+                    attributes: Some(vec![Trait {
+                        display_type: None,
+                        trait_type: "color".to_string(),
+                        value: color.to_string(),
+                    }]),
+                    ..Metadata::default()
+                }),
+            };
+            contract
+                .tokens
+                .save(&mut deps.storage, token_id, &token)
+                .unwrap();
+        }
+        // Buy counted sold tokens a second time
+        contract.token_count.save(&mut deps.storage, &5).unwrap();
+
+        let migrate_msg = MigrateMsg {
+            config: Some(MigrateConfig {
+                token_supply: Some(20),
+                buy_amount: 200,
+                available: false,
+                base_uri: None,
+                token_uri_suffix: None,
+                history_retention: None,
+                progression: None,
+                transferable: true,
+                transfer_lock: None,
+                breed_cooldown: None,
+            }),
+        };
+        let res = migrate(deps.as_mut(), mock_env(), migrate_msg.clone()).unwrap();
+        assert_eq!("0.10.0", res.attributes[2].value);
+
+        let version = get_contract_version(&deps.storage).unwrap();
+        assert_eq!(CONTRACT_NAME, version.contract);
+        assert_eq!(CONTRACT_VERSION, version.version);
+        assert!(SALES_STATS.may_load(&deps.storage).unwrap().is_some());
+        assert_eq!(2, SALES.load(&deps.storage).unwrap().last_token_id);
+
+        // Config is replaced but the contract stays frozen
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert_eq!(Some(20), config.token_supply);
+        assert_eq!(200, config.buy_amount);
+        assert!(config.frozen);
+        assert!(config.transferable);
+
+        // Tokens cannot be minted or burned until reindexed
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "4".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg);
+        assert_eq!(ContractError::Reindexing {}, res.unwrap_err());
+        let burn_msg = ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(OWNER, &[]),
+            burn_msg.clone(),
+        );
+        assert_eq!(ContractError::Reindexing {}, res.unwrap_err());
+
+        // Legacy tokens are reindexed a page at a time by the minter
+        let exec_msg = ExecuteMsg::Reindex { limit: Some(2) };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg.clone()).unwrap();
+        assert_eq!(
+            vec![Event::new("rest_nft.reindex")
+                .add_attribute("version", "1")
+                .add_attribute("last_token_id", "2")
+                .add_attribute("token_count", "2")
+                .add_attribute("done", "false")],
+            res.events
+        );
+        assert_eq!(5, contract.token_count(&deps.storage).unwrap());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg.clone()).unwrap();
+        assert_eq!(3, contract.token_count(&deps.storage).unwrap());
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg);
+        assert_eq!(ContractError::NoReindex {}, res.unwrap_err());
+
+        let query_msg = QueryMsg::TraitCounts {
+            trait_type: "color".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: TraitCountsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![
+                TraitCount {
+                    trait_type: "color".to_string(),
+                    value: "blue".to_string(),
+                    count: 1,
+                },
+                TraitCount {
+                    trait_type: "color".to_string(),
+                    value: "red".to_string(),
+                    count: 2,
+                },
+            ],
+            res.counts
+        );
+        assert_eq!(
+            Some(mock_env().block.time),
+            HELD_SINCE.may_load(&deps.storage, "3").unwrap()
+        );
+
+        // Reindexed tokens keep their counts in sync when burned
+        execute(deps.as_mut(), mock_env(), mock_info(OWNER, &[]), burn_msg).unwrap();
+        assert_eq!(
+            1,
+            TRAIT_COUNTS.load(&deps.storage, ("color", "red")).unwrap()
+        );
+
+        // Migrating to the same version runs no migrations
+        let res = migrate(deps.as_mut(), mock_env(), MigrateMsg { config: None }).unwrap();
+        assert_eq!("", res.attributes[2].value);
+
+        // Downgrades are refused
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "99.0.0").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), migrate_msg.clone());
+        assert_eq!(
+            ContractError::CannotDowngrade {
+                stored: "99.0.0".to_string(),
+                new: CONTRACT_VERSION.to_string(),
+            },
+            res.unwrap_err()
+        );
+
+        set_contract_version(&mut deps.storage, CONTRACT_NAME, "latest").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), migrate_msg.clone());
+        assert_eq!(
+            ContractError::InvalidVersion {
+                version: "latest".to_string(),
+            },
+            res.unwrap_err()
+        );
+
+        // Legacy versions are never compared
+        set_contract_version(&mut deps.storage, LEGACY_CONTRACT_NAME, "latest").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), migrate_msg.clone()).unwrap();
+        assert_eq!("0.10.0", res.attributes[2].value);
+
+        // Other contracts are refused
+        set_contract_version(&mut deps.storage, "crates.io:cw20-base", "0.9.0").unwrap();
+        let res = migrate(deps.as_mut(), mock_env(), migrate_msg);
+        assert_eq!(
+            ContractError::WrongContract {
+                expected: CONTRACT_NAME.to_string(),
+                actual: "crates.io:cw20-base".to_string(),
+            },
+            res.unwrap_err()
+        );
+    }
+
+    #[test]
+    fn instantiate_validation() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: Some(10),
+            buy_amount: 100,
+            available: false,
+            collection_info: None,
+        };
+
+        let mut msg = init_msg.clone();
+        msg.name = "SS".to_string();
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::InvalidName { min: 3, max: 50 },
+            res.unwrap_err()
+        );
+
+        let mut msg = init_msg.clone();
+        msg.name = "S".repeat(51);
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(
+            ContractError::InvalidName { min: 3, max: 50 },
+            res.unwrap_err()
+        );
+
+        for symbol in ["SP", "SPACESHIPSXYZ", "SPACE1", "SPA CE"] {
+            let mut msg = init_msg.clone();
+            msg.symbol = symbol.to_string();
+            let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+            assert_eq!(
+                ContractError::InvalidSymbol { min: 3, max: 12 },
+                res.unwrap_err()
+            );
+        }
+
+        let mut msg = init_msg.clone();
+        msg.minter = "a".to_string();
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert!(matches!(res.unwrap_err(), ContractError::Std(_)));
+
+        let mut msg = init_msg.clone();
+        msg.token_supply = Some(0);
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(ContractError::InvalidTokenSupply {}, res.unwrap_err());
+
+        let mut msg = init_msg.clone();
+        msg.buy_amount = 0;
+        msg.available = true;
+        let res = instantiate(deps.as_mut(), mock_env(), info.clone(), msg);
+        assert_eq!(ContractError::InvalidSale {}, res.unwrap_err());
+
+        // Nothing was stored by the rejected messages
+        assert!(CONFIG.may_load(&deps.storage).unwrap().is_none());
+
+        // available is honored
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+        let config = CONFIG.load(&deps.storage).unwrap();
+        assert!(!config.available);
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: CREATOR.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Buy {
+            recipient: OWNER.to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(OWNER, &coins(100, "uusd")),
+            exec_msg,
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+
+        // The sale cannot be opened at a zero price
+        let exec_msg = ExecuteMsg::SetBuyAmount { buy_amount: 0 };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::SetAvailable { available: true };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg);
+        assert_eq!(ContractError::InvalidSale {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn pause() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        // Only the minter sets the guardian, only the guardian pauses
+        let guardian = mock_info("guardian", &[]);
+        let exec_msg = ExecuteMsg::SetGuardian {
+            guardian: Some("guardian".to_string()),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Pause {},
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            guardian.clone(),
+            ExecuteMsg::Pause {},
+        )
+        .unwrap();
+
+        let res: PauseStatusResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PauseStatus {}).unwrap())
+                .unwrap();
+        assert!(res.paused);
+        assert_eq!(Some("guardian"), res.guardian.as_ref().map(|g| g.as_str()));
+        assert_eq!(Operation::ALL.to_vec(), res.paused_operations);
+
+        // Every operation class is blocked by default
+        let transfer_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "1".to_string(),
+        };
+        let owner = mock_info(OWNER, &[]);
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner.clone(),
+            transfer_msg.clone(),
+        );
+        assert_eq!(ContractError::Paused {}, res.unwrap_err());
+
+        let exec_msg = ExecuteMsg::ApproveAll {
+            operator: PUBLIC.to_string(),
+            expires: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::Paused {}, res.unwrap_err());
+
+        let burn_msg = ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), burn_msg.clone());
+        assert_eq!(ContractError::Paused {}, res.unwrap_err());
+
+        let mint_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "2".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), mint_msg.clone());
+        assert_eq!(ContractError::Paused {}, res.unwrap_err());
+
+        // Only the configured classes are blocked
+        let exec_msg = ExecuteMsg::SetPausedOperations {
+            operations: vec![Operation::Transfer, Operation::Transfer],
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), guardian.clone(), exec_msg).unwrap();
+
+        execute(deps.as_mut(), mock_env(), info, mint_msg).unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner.clone(),
+            transfer_msg.clone(),
+        );
+        assert_eq!(ContractError::Paused {}, res.unwrap_err());
+
+        let res: PauseStatusResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), QueryMsg::PauseStatus {}).unwrap())
+                .unwrap();
+        assert_eq!(vec![Operation::Transfer], res.paused_operations);
+
+        execute(deps.as_mut(), mock_env(), guardian, ExecuteMsg::Unpause {}).unwrap();
+        execute(deps.as_mut(), mock_env(), owner, transfer_msg).unwrap();
+        execute(deps.as_mut(), mock_env(), mock_info(PUBLIC, &[]), burn_msg).unwrap();
+    }
+
+    #[test]
+    fn soulbound() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for token_id in ["1", "2"] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: None,
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        // Only the minter marks tokens soulbound
+        let exec_msg = ExecuteMsg::SetTransferable {
+            token_id: Some("1".to_string()),
+            transferable: false,
+        };
+        let owner = mock_info(OWNER, &[]);
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::Transferable {
+            token_id: Some("1".to_string()),
+        };
+        let res: TransferableResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert!(!res.transferable);
+
+        let rejected = [
+            ExecuteMsg::TransferNft {
+                recipient: PUBLIC.to_string(),
+                token_id: "1".to_string(),
+            },
+            ExecuteMsg::SendNft {
+                contract: "market".to_string(),
+                token_id: "1".to_string(),
+                msg: to_binary("list").unwrap(),
+            },
+            ExecuteMsg::Approve {
+                spender: PUBLIC.to_string(),
+                token_id: "1".to_string(),
+                expires: None,
+            },
+        ];
+        for exec_msg in rejected {
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+            assert_eq!(ContractError::NonTransferable {}, res.unwrap_err());
+        }
+
+        // Other tokens still move
+        let exec_msg = ExecuteMsg::Approve {
+            spender: PUBLIC.to_string(),
+            token_id: "2".to_string(),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+
+        // A soulbound collection also rejects ApproveAll
+        let exec_msg = ExecuteMsg::SetTransferable {
+            token_id: None,
+            transferable: false,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::ApproveAll {
+            operator: PUBLIC.to_string(),
+            expires: None,
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::NonTransferable {}, res.unwrap_err());
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "2".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::NonTransferable {}, res.unwrap_err());
+
+        // The minter can recover soulbound tokens
+        let exec_msg = ExecuteMsg::RecoverToken {
+            token_id: "1".to_string(),
+            recipient: PUBLIC.to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let res = RestNFTContract::default()
+            .owner_of(deps.as_ref(), mock_env(), "1".to_string(), false)
+            .unwrap();
+        assert_eq!(PUBLIC, res.owner);
+
+        // Owners can still burn
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "2".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), owner, exec_msg).unwrap();
+    }
+
+    #[test]
+    fn transfer_lock() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 100,
+            available: true,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for token_id in ["1", "2", "3", "4"] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: CREATOR.to_string(),
+                token_uri: None,
+                extension: None,
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        let exec_msg = ExecuteMsg::SetTransferLock {
+            duration: Some(Duration::Time(3600)),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(OWNER, &[]),
+            exec_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        for _ in 0..3 {
+            let exec_msg = ExecuteMsg::Buy {
+                recipient: OWNER.to_string(),
+            };
+            execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(OWNER, &coins(100, "uusd")),
+                exec_msg,
+            )
+            .unwrap();
+        }
+
+        let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(3600));
+        let query_msg = QueryMsg::TransferLock {
+            token_id: "1".to_string(),
+        };
+        let res: TransferLockResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+        assert_eq!(Some(expires), res.expires);
+
+        // Transfers and sends are locked
+        let owner = mock_info(OWNER, &[]);
+        let transfer_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "1".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner.clone(),
+            transfer_msg.clone(),
+        );
+        assert_eq!(ContractError::TransferLocked { expires }, res.unwrap_err());
+        let exec_msg = ExecuteMsg::SendNft {
+            contract: "market".to_string(),
+            token_id: "1".to_string(),
+            msg: to_binary("list").unwrap(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::TransferLocked { expires }, res.unwrap_err());
+
+        // Tokens not bought through Buy are not locked
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "4".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        // A token minted again after a burn does not inherit the lock
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "2".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "2".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "2".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+
+        // Recovering a token lifts its lock
+        let exec_msg = ExecuteMsg::SetTransferable {
+            token_id: Some("3".to_string()),
+            transferable: false,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::RecoverToken {
+            token_id: "3".to_string(),
+            recipient: PUBLIC.to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let res: TransferLockResponse = from_binary(
+            &query(
+                deps.as_ref(),
+                mock_env(),
+                QueryMsg::TransferLock {
+                    token_id: "3".to_string(),
+                },
+            )
+            .unwrap(),
+        )
+        .unwrap();
+        assert_eq!(None, res.expires);
+
+        // The lock lifts once expired
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(3600);
+        let res: TransferLockResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
+        assert_eq!(None, res.expires);
+        execute(deps.as_mut(), env, owner, transfer_msg).unwrap();
+    }
+
+    #[test]
+    fn staking() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for token_id in ["1", "2"] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: None,
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        // Only the owner stakes
+        let stake_msg = ExecuteMsg::Stake {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, stake_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let owner = mock_info(OWNER, &[]);
+        execute(deps.as_mut(), mock_env(), owner.clone(), stake_msg.clone()).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), stake_msg.clone());
+        assert_eq!(ContractError::TokenStaked {}, res.unwrap_err());
+
+        // Staked tokens cannot move or burn
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::TokenStaked {}, res.unwrap_err());
+        let burn_msg = ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), burn_msg.clone());
+        assert_eq!(ContractError::TokenStaked {}, res.unwrap_err());
+
+        // but stay listed under the owner
+        let res = RestNFTContract::default()
+            .tokens(deps.as_ref(), OWNER.to_string(), None, None)
+            .unwrap();
+        assert_eq!(vec!["1", "2"], res.tokens);
+
+        let query_msg = QueryMsg::StakedTokens {
+            owner: OWNER.to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: TokensResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(vec!["1"], res.tokens);
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(100);
+        let query_msg = QueryMsg::StakeInfo {
+            token_id: "1".to_string(),
+        };
+        let res: StakeInfoResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), query_msg.clone()).unwrap()).unwrap();
+        assert!(res.staked);
+        assert_eq!(Some(mock_env().block.time), res.staked_since);
+        assert_eq!(100, res.total_staked_seconds);
+
+        let unstake_msg = ExecuteMsg::Unstake {
+            token_id: "1".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info(PUBLIC, &[]),
+            unstake_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(
+            deps.as_mut(),
+            env.clone(),
+            owner.clone(),
+            unstake_msg.clone(),
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            owner.clone(),
+            unstake_msg.clone(),
+        );
+        assert_eq!(ContractError::TokenNotStaked {}, res.unwrap_err());
+
+        // Duration accumulates over stakes
+        execute(deps.as_mut(), env.clone(), owner.clone(), stake_msg).unwrap();
+        env.block.time = env.block.time.plus_seconds(50);
+        execute(deps.as_mut(), env.clone(), owner.clone(), unstake_msg).unwrap();
+        let res: StakeInfoResponse =
+            from_binary(&query(deps.as_ref(), env.clone(), query_msg).unwrap()).unwrap();
+        assert!(!res.staked);
+        assert_eq!(None, res.owner);
+        assert_eq!(150, res.total_staked_seconds);
+
+        execute(deps.as_mut(), env, owner, burn_msg).unwrap();
+    }
+
+    #[test]
+    fn fusion() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for (token_id, level) in [("1", "2"), ("2", "3"), ("3", "1")] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: Some(Metadata {
+                    attributes: Some(vec![Trait {
+                        display_type: None,
+                        trait_type: "level".to_string(),
+                        value: level.to_string(),
+                    }]),
+                    ..Metadata::default()
+                }),
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        let recipe = Recipe {
+            input_count: 2,
+            token_uri: Some("ipfs://king".to_string()),
+            metadata: Metadata {
+                name: Some("King Toad".to_string()),
+                ..Metadata::default()
+            },
+            level_inheritance: Some(LevelInheritance::Max),
+        };
+        let exec_msg = ExecuteMsg::SetRecipe {
+            recipe_id: "king".to_string(),
+            recipe: recipe.clone(),
+        };
+        let owner = mock_info(OWNER, &[]);
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::Recipes {
+            start_after: None,
+            limit: None,
+        };
+        let res: RecipesResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(1, res.recipes.len());
+        assert_eq!("king", res.recipes[0].recipe_id);
+        assert_eq!(recipe, res.recipes[0].recipe);
+
+        // Inputs must match the recipe and be distinct
+        for token_ids in [vec!["1"], vec!["1", "1"], vec!["1", "2", "3"]] {
+            let exec_msg = ExecuteMsg::Fuse {
+                token_ids: token_ids.iter().map(|id| id.to_string()).collect(),
+                recipe_id: "king".to_string(),
+            };
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+            assert_eq!(
+                ContractError::InvalidRecipeInputs { expected: 2 },
+                res.unwrap_err()
+            );
+        }
+
+        // Only tokens the caller can send are fused
+        let fuse_msg = ExecuteMsg::Fuse {
+            token_ids: vec!["1".to_string(), "2".to_string()],
+            recipe_id: "king".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            fuse_msg.clone(),
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), fuse_msg).unwrap();
+        assert_eq!("fused-1", res.attributes[3].value);
+
+        let contract = RestNFTContract::default();
+        let res = contract
+            .tokens(deps.as_ref(), OWNER.to_string(), None, None)
+            .unwrap();
+        assert_eq!(vec!["3", "fused-1"], res.tokens);
+        assert_eq!(2, contract.num_tokens(deps.as_ref()).unwrap().count);
+
+        // The fused token takes the recipe metadata and the highest level
+        let token = contract
+            .nft_info(deps.as_ref(), "fused-1".to_string())
+            .unwrap();
+        assert_eq!(Some("ipfs://king".to_string()), token.token_uri);
+        let metadata = token.extension.unwrap();
+        assert_eq!(Some("King Toad".to_string()), metadata.name);
+        assert_eq!("3", metadata.attributes.unwrap()[0].value);
+
+        let exec_msg = ExecuteMsg::RemoveRecipe {
+            recipe_id: "king".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::Fuse {
+            token_ids: vec!["3".to_string(), "fused-1".to_string()],
+            recipe_id: "king".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::RecipeNotFound {}, res.unwrap_err());
+
+        // Summed levels saturate instead of overflowing, and ids the minter
+        // already took are skipped
+        for (token_id, level) in [("4", "4000000000"), ("5", "3000000000"), ("fused-2", "1")] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: Some(Metadata {
+                    attributes: Some(vec![Trait {
+                        display_type: None,
+                        trait_type: "level".to_string(),
+                        value: level.to_string(),
+                    }]),
+                    ..Metadata::default()
+                }),
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+        let exec_msg = ExecuteMsg::SetRecipe {
+            recipe_id: "emperor".to_string(),
+            recipe: Recipe {
+                level_inheritance: Some(LevelInheritance::Sum),
+                ..recipe
+            },
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::Fuse {
+            token_ids: vec!["4".to_string(), "5".to_string()],
+            recipe_id: "emperor".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+        assert_eq!("fused-3", res.attributes[3].value);
+        let token = contract
+            .nft_info(deps.as_ref(), "fused-3".to_string())
+            .unwrap();
+        assert_eq!(
+            u32::MAX.to_string(),
+            token.extension.unwrap().attributes.unwrap()[0].value
+        );
+
+        // Inherited levels are capped at the highest level the thresholds
+        // reach, so experience never demotes the fused token
+        let exec_msg = ExecuteMsg::SetProgression {
+            game_server: "game".to_string(),
+            level_thresholds: vec![0, 100],
+            max_level: 5,
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        for (token_id, level) in [("6", "4"), ("7", "3")] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: Some(Metadata {
+                    attributes: Some(vec![Trait {
+                        display_type: None,
+                        trait_type: "level".to_string(),
+                        value: level.to_string(),
+                    }]),
+                    ..Metadata::default()
+                }),
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+        let exec_msg = ExecuteMsg::Fuse {
+            token_ids: vec!["6".to_string(), "7".to_string()],
+            recipe_id: "emperor".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::AddExperience {
+            token_id: "fused-4".to_string(),
+            amount: 1,
+        };
+        let res = execute(deps.as_mut(), mock_env(), mock_info("game", &[]), exec_msg).unwrap();
+        assert!(res.events.is_empty());
+        assert!(res.attributes.contains(&attr("level", "2")));
+        assert!(res.attributes.contains(&attr("experience", "101")));
+    }
+
+    #[test]
+    fn breeding() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: Some(4),
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for (token_id, color) in [("1", "red"), ("2", "blue"), ("3", "green")] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: Some(Metadata {
+                    attributes: Some(vec![
+                        Trait {
+                            display_type: None,
+                            trait_type: "color".to_string(),
+                            value: color.to_string(),
+                        },
+                        Trait {
+                            display_type: None,
+                            trait_type: "level".to_string(),
+                            value: "5".to_string(),
+                        },
+                    ]),
+                    ..Metadata::default()
+                }),
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        let breed_msg = ExecuteMsg::Breed {
+            parent_a: "1".to_string(),
+            parent_b: "2".to_string(),
+        };
+        let owner = mock_info(OWNER, &[]);
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), breed_msg.clone());
+        assert_eq!(ContractError::BreedingDisabled {}, res.unwrap_err());
+
+        let exec_msg = ExecuteMsg::SetBreeding {
+            cooldown: Some(Duration::Time(3600)),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        // only the owner can breed its tokens, and from two of them
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), breed_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let exec_msg = ExecuteMsg::Breed {
+            parent_a: "1".to_string(),
+            parent_b: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::InvalidParents {}, res.unwrap_err());
+
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), breed_msg.clone()).unwrap();
+        let breed = res
+            .events
+            .iter()
+            .find(|event| event.ty == "rest_nft.breed")
+            .unwrap();
+        assert!(breed.attributes.contains(&attr("token_id", "bred-1")));
+        assert!(breed.attributes.contains(&attr("generation", "1")));
+
+        let query_msg = QueryMsg::NftInfo {
+            token_id: "bred-1".to_string(),
+        };
+        let res: NftInfoResponse<Extension> =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        let attributes = res.extension.unwrap().attributes.unwrap();
+        assert_eq!(1, attributes.len());
+        assert_eq!("color", attributes[0].trait_type);
+        assert!(["red", "blue"].contains(&attributes[0].value.as_str()));
+
+        let query_msg = QueryMsg::Lineage {
+            token_id: "bred-1".to_string(),
+        };
+        let res: LineageResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(vec!["1".to_string(), "2".to_string()], res.lineage.parents);
+        assert_eq!(1, res.lineage.generation);
+        assert_eq!(None, res.cooldown);
+
+        // parents are cooling down
+        let exec_msg = ExecuteMsg::Breed {
+            parent_a: "3".to_string(),
+            parent_b: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg.clone());
+        let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(3600));
+        assert_eq!(ContractError::BreedCooldown { expires }, res.unwrap_err());
+        let query_msg = QueryMsg::Lineage {
+            token_id: "1".to_string(),
+        };
+        let res: LineageResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(0, res.lineage.generation);
+        assert_eq!(Some(expires), res.cooldown);
+
+        // children count against the token supply
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(3600);
+        let exec_msg = ExecuteMsg::Breed {
+            parent_a: "bred-1".to_string(),
+            parent_b: "3".to_string(),
+        };
+        let res = execute(deps.as_mut(), env.clone(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::MaxTokenSupply {}, res.unwrap_err());
+
+        // ids the minter already took are skipped
+        for token_id in ["2", "3"] {
+            let exec_msg = ExecuteMsg::Burn {
+                token_id: token_id.to_string(),
+            };
+            execute(deps.as_mut(), env.clone(), owner.clone(), exec_msg).unwrap();
+        }
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "bred-2".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), env.clone(), info, exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::Breed {
+            parent_a: "bred-1".to_string(),
+            parent_b: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), env, owner, exec_msg).unwrap();
+        assert!(res.attributes.contains(&attr("token_id", "bred-3")));
+    }
+
+    #[test]
+    fn evolution() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: Some("ipfs://egg".to_string()),
+            extension: Some(Metadata {
+                name: Some("Egg".to_string()),
+                attributes: Some(vec![Trait {
+                    display_type: None,
+                    trait_type: "level".to_string(),
+                    value: "2".to_string(),
+                }]),
+                ..Metadata::default()
+            }),
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let evolution = Evolution {
+            metadata: Metadata {
+                name: Some("Toad".to_string()),
+                image: Some("ipfs://toad.png".to_string()),
+                ..Metadata::default()
+            },
+            token_uri: Some("ipfs://toad".to_string()),
+            min_level: Some(3),
+            min_hold_seconds: Some(86400),
+            fee: Some(coin(50, "uusd")),
+        };
+        let exec_msg = ExecuteMsg::SetEvolution {
+            stage: "Egg".to_string(),
+            evolution: evolution.clone(),
+        };
+        let owner = mock_info(OWNER, &[]);
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::Evolutions {
+            start_after: None,
+            limit: None,
+        };
+        let res: EvolutionsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(1, res.evolutions.len());
+        assert_eq!("Egg", res.evolutions[0].stage);
+        assert_eq!(evolution, res.evolutions[0].evolution);
+
+        let exec_msg = ExecuteMsg::SetEvolution {
+            stage: "Toad".to_string(),
+            evolution: Evolution {
+                metadata: Metadata::default(),
+                token_uri: None,
+                min_level: None,
+                min_hold_seconds: Some(u64::MAX),
+                fee: None,
+            },
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        // the table is fixed once frozen, but evolving still works
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            info.clone(),
+            ExecuteMsg::Freeze {},
+        )
+        .unwrap();
+        let exec_msg = ExecuteMsg::RemoveEvolution {
+            stage: "Egg".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), exec_msg);
+        assert_eq!(ContractError::ContractFrozen {}, res.unwrap_err());
+
+        let evolve_msg = ExecuteMsg::Evolve {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), evolve_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), evolve_msg.clone());
+        assert_eq!(
+            ContractError::LevelTooLow { min_level: 3 },
+            res.unwrap_err()
+        );
+
+        let exec_msg = ExecuteMsg::SetLevel {
+            token_id: "1".to_string(),
+            level: "3".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), evolve_msg.clone());
+        let until = mock_env().block.time.plus_seconds(86400);
+        assert_eq!(ContractError::HeldTooShort { until }, res.unwrap_err());
+
+        let mut env = mock_env();
+        env.block.time = until;
+        for funds in [
+            vec![],
+            coins(60, "uusd"),
+            vec![coin(50, "uusd"), coin(1, "uluna")],
+        ] {
+            let res = execute(
+                deps.as_mut(),
+                env.clone(),
+                mock_info(OWNER, &funds),
+                evolve_msg.clone(),
+            );
+            assert_eq!(ContractError::Funds {}, res.unwrap_err());
+        }
+
+        let payer = mock_info(OWNER, &coins(50, "uusd"));
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            payer.clone(),
+            evolve_msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: CREATOR.to_string(),
+                amount: coins(50, "uusd"),
+            }),
+            res.messages[0].msg
+        );
+        let evolve = res
+            .events
+            .iter()
+            .find(|event| event.ty == "rest_nft.evolve")
+            .unwrap();
+        assert!(evolve.attributes.contains(&attr("old_stage", "Egg")));
+        assert!(evolve.attributes.contains(&attr("new_stage", "Toad")));
+
+        // the level is kept through the evolution
+        let query_msg = QueryMsg::NftInfo {
+            token_id: "1".to_string(),
+        };
+        let res: NftInfoResponse<Extension> =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(Some("ipfs://toad".to_string()), res.token_uri);
+        let metadata = res.extension.unwrap();
+        assert_eq!(Some("Toad".to_string()), metadata.name);
+        assert_eq!(Some("ipfs://toad.png".to_string()), metadata.image);
+        assert_eq!("3", metadata.attributes.unwrap()[0].value);
+
+        // hold times too long to ever end are an error rather than a panic
+        let res = execute(deps.as_mut(), env.clone(), owner.clone(), evolve_msg);
+        assert!(matches!(res.unwrap_err(), ContractError::Std(_)));
+
+        // no evolution from the last stage
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "2".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: Some(Metadata {
+                name: Some("Frog".to_string()),
+                ..Metadata::default()
+            }),
+        });
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let evolve_msg = ExecuteMsg::Evolve {
+            token_id: "2".to_string(),
+        };
+        let res = execute(deps.as_mut(), env, owner, evolve_msg);
+        assert_eq!(ContractError::EvolutionNotFound {}, res.unwrap_err());
+    }
+
+    #[test]
+    fn equipment() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        for token_id in ["1", "2"] {
+            let exec_msg = ExecuteMsg::Mint(MintMsg {
+                token_id: token_id.to_string(),
+                owner: OWNER.to_string(),
+                token_uri: None,
+                extension: None,
+            });
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+
+        let equip_msg = ExecuteMsg::Equip {
+            token_id: "1".to_string(),
+            child_token_id: "2".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), info.clone(), equip_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let owner = mock_info(OWNER, &[]);
+        let exec_msg = ExecuteMsg::Equip {
+            token_id: "1".to_string(),
+            child_token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::InvalidChild {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), owner.clone(), equip_msg).unwrap();
+
+        // the child is held by the contract and moves with its parent
+        let contract = RestNFTContract::default();
+        let res = contract
+            .owner_of(deps.as_ref(), mock_env(), "2".to_string(), false)
+            .unwrap();
+        assert_eq!(MOCK_CONTRACT_ADDR, res.owner);
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "1".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+
+        // external tokens and cw20 balances are equipped by the parent owner,
+        // from contracts allowed by the minter
+        let receive_msg = to_binary(&ReceiveMsg::Equip {
+            token_id: "1".to_string(),
+        })
+        .unwrap();
+        let exec_msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: PUBLIC.to_string(),
+            token_id: "sword".to_string(),
+            msg: receive_msg.clone(),
+        });
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("armory", &[]),
+            exec_msg,
+        );
+        assert_eq!(ContractError::NotChildContract {}, res.unwrap_err());
+        for contract in ["armory", "gold"] {
+            let exec_msg = ExecuteMsg::SetChildContract {
+                contract: contract.to_string(),
+            };
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg.clone());
+            assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+            execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+        }
+        let exec_msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: OWNER.to_string(),
+            token_id: "sword".to_string(),
+            msg: receive_msg.clone(),
+        });
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("armory", &[]),
+            exec_msg,
+        );
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let exec_msg = ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+            sender: PUBLIC.to_string(),
+            token_id: "sword".to_string(),
+            msg: receive_msg.clone(),
+        });
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("armory", &[]),
+            exec_msg,
+        )
+        .unwrap();
+        let exec_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: PUBLIC.to_string(),
+            amount: Uint128::new(100),
+            msg: receive_msg,
+        });
+        execute(deps.as_mut(), mock_env(), mock_info("gold", &[]), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::Equipped {
+            token_id: "1".to_string(),
+        };
+        let res: EquippedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(None, res.parent);
+        assert_eq!(
+            vec![
+                Child::Cw721 {
+                    contract: "armory".to_string(),
+                    token_id: "sword".to_string(),
+                },
+                Child::Token {
+                    token_id: "2".to_string(),
+                },
+                Child::Cw20 {
+                    contract: "gold".to_string(),
+                    amount: Uint128::new(100),
+                },
+            ],
+            res.children
+        );
+        let query_msg = QueryMsg::Equipped {
+            token_id: "2".to_string(),
+        };
+        let res: EquippedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(Some("1".to_string()), res.parent);
+
+        let public = mock_info(PUBLIC, &[]);
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), public.clone(), exec_msg);
+        assert_eq!(ContractError::TokenHasChildren {}, res.unwrap_err());
+
+        // unequipped children go to the parent owner
+        let exec_msg = ExecuteMsg::Unequip {
+            token_id: "1".to_string(),
+            child: Child::Cw20 {
+                contract: "gold".to_string(),
+                amount: Uint128::new(40),
+            },
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let res = execute(deps.as_mut(), mock_env(), public.clone(), exec_msg).unwrap();
+        assert_eq!(
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "gold".to_string(),
+                msg: to_binary(&Cw20ExecuteMsg::Transfer {
+                    recipient: PUBLIC.to_string(),
+                    amount: Uint128::new(40),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            res.messages[0].msg
+        );
+
+        let exec_msg = ExecuteMsg::Unequip {
+            token_id: "1".to_string(),
+            child: Child::Cw721 {
+                contract: "armory".to_string(),
+                token_id: "sword".to_string(),
+            },
+        };
+        let res = execute(deps.as_mut(), mock_env(), public.clone(), exec_msg).unwrap();
+        assert_eq!(
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "armory".to_string(),
+                msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: PUBLIC.to_string(),
+                    token_id: "sword".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            res.messages[0].msg
+        );
+
+        let exec_msg = ExecuteMsg::Unequip {
+            token_id: "1".to_string(),
+            child: Child::Token {
+                token_id: "2".to_string(),
+            },
+        };
+        execute(deps.as_mut(), mock_env(), public.clone(), exec_msg.clone()).unwrap();
+        let res = contract
+            .owner_of(deps.as_ref(), mock_env(), "2".to_string(), false)
+            .unwrap();
+        assert_eq!(PUBLIC, res.owner);
+        let res = execute(deps.as_mut(), mock_env(), public.clone(), exec_msg);
+        assert_eq!(ContractError::ChildNotFound {}, res.unwrap_err());
+
+        let query_msg = QueryMsg::Equipped {
+            token_id: "1".to_string(),
+        };
+        let res: EquippedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+        assert_eq!(
+            vec![Child::Cw20 {
+                contract: "gold".to_string(),
+                amount: Uint128::new(60),
+            }],
+            res.children
+        );
+
+        // children of removed contracts are no longer accepted
+        let exec_msg = ExecuteMsg::RemoveChildContract {
+            contract: "gold".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+        let exec_msg = ExecuteMsg::Receive(Cw20ReceiveMsg {
+            sender: PUBLIC.to_string(),
+            amount: Uint128::new(100),
+            msg: to_binary(&ReceiveMsg::Equip {
+                token_id: "1".to_string(),
+            })
+            .unwrap(),
+        });
+        let res = execute(deps.as_mut(), mock_env(), mock_info("gold", &[]), exec_msg);
+        assert_eq!(ContractError::NotChildContract {}, res.unwrap_err());
+
+        // detached children are dropped without being sent back
+        let exec_msg = ExecuteMsg::Detach {
+            token_id: "1".to_string(),
+            child: Child::Token {
+                token_id: "2".to_string(),
+            },
+        };
+        let res = execute(deps.as_mut(), mock_env(), public.clone(), exec_msg);
+        assert_eq!(ContractError::InvalidChild {}, res.unwrap_err());
+        let exec_msg = ExecuteMsg::Detach {
+            token_id: "1".to_string(),
+            child: Child::Cw20 {
+                contract: "gold".to_string(),
+                amount: Uint128::new(60),
+            },
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner, exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let res = execute(deps.as_mut(), mock_env(), public.clone(), exec_msg).unwrap();
+        assert!(res.messages.is_empty());
+        assert_eq!(
+            vec![Event::new("rest_nft.detach")
+                .add_attribute("version", "1")
+                .add_attribute("token_id", "1")
+                .add_attribute("child", r#"{"cw20":{"contract":"gold","amount":"60"}}"#)],
+            res.events
+        );
+        let res: EquippedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert!(res.children.is_empty());
+        let exec_msg = ExecuteMsg::Burn {
+            token_id: "1".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), public, exec_msg).unwrap();
+    }
+
+    #[test]
+    fn exchange() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "v2-1".to_string(),
+            owner: CREATOR.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let source = ExchangeSource {
+            token_id_prefix: Some("v2-".to_string()),
+            forward_to: Some("burn".to_string()),
+        };
+        let exec_msg = ExecuteMsg::SetExchangeSource {
+            contract: "legacy".to_string(),
+            source: source.clone(),
+        };
+        let owner = mock_info(OWNER, &[]);
+        let res = execute(deps.as_mut(), mock_env(), owner, exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let query_msg = QueryMsg::ExchangeSources {
+            start_after: None,
+            limit: None,
+        };
+        let res: ExchangeSourcesResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!("legacy", res.sources[0].contract);
+        assert_eq!(source, res.sources[0].source);
+
+        let exchange_msg = |token_id: &str| {
+            ExecuteMsg::ReceiveNft(Cw721ReceiveMsg {
+                sender: OWNER.to_string(),
+                token_id: token_id.to_string(),
+                msg: to_binary(&ReceiveMsg::Exchange {}).unwrap(),
+            })
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("other", &[]),
+            exchange_msg("1"),
+        );
+        assert_eq!(ContractError::NotExchangeSource {}, res.unwrap_err());
+
+        // issued from the minter inventory, the source token is forwarded
+        let legacy = mock_info("legacy", &[]);
+        let res = execute(deps.as_mut(), mock_env(), legacy.clone(), exchange_msg("1")).unwrap();
+        assert_eq!(
+            CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "legacy".to_string(),
+                msg: to_binary(&Cw721ExecuteMsg::TransferNft {
+                    recipient: "burn".to_string(),
+                    token_id: "1".to_string(),
+                })
+                .unwrap(),
+                funds: vec![],
+            }),
+            res.messages[0].msg
+        );
+        let res = execute(deps.as_mut(), mock_env(), legacy.clone(), exchange_msg("1"));
+        assert_eq!(ContractError::Claimed {}, res.unwrap_err());
+
+        // minted when missing from the inventory
+        execute(deps.as_mut(), mock_env(), legacy, exchange_msg("2")).unwrap();
+
+        let contract = RestNFTContract::default();
+        for token_id in ["v2-1", "v2-2"] {
+            let res = contract
+                .owner_of(deps.as_ref(), mock_env(), token_id.to_string(), false)
+                .unwrap();
+            assert_eq!(OWNER, res.owner);
+        }
+
+        let query_msg = QueryMsg::Exchanged {
+            source_contract: "legacy".to_string(),
+            source_token_id: "2".to_string(),
+        };
+        let res: ExchangedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(
+            Some(ExchangeRecord {
+                token_id: "v2-2".to_string(),
+                recipient: Addr::unchecked(OWNER),
+                time: mock_env().block.time,
+            }),
+            res.exchange
+        );
+        let query_msg = QueryMsg::Exchanged {
+            source_contract: "legacy".to_string(),
+            source_token_id: "3".to_string(),
+        };
+        let res: ExchangedResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(None, res.exchange);
+    }
+
+    #[test]
+    fn token_user() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(3600));
+        let set_user_msg = ExecuteMsg::SetUser {
+            token_id: "1".to_string(),
+            user: Some("scholar".to_string()),
+            expires,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, set_user_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let exec_msg = ExecuteMsg::SetUser {
+            token_id: "1".to_string(),
+            user: Some("scholar".to_string()),
+            expires: Expiration::AtHeight(mock_env().block.height),
+        };
+        let owner = mock_info(OWNER, &[]);
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::InvalidExpiration {}, res.unwrap_err());
+
+        // approved spenders can set the user too
+        let exec_msg = ExecuteMsg::Approve {
+            spender: PUBLIC.to_string(),
+            token_id: "1".to_string(),
+            expires: None,
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info(PUBLIC, &[]),
+            set_user_msg,
+        )
+        .unwrap();
+
+        let query_msg = QueryMsg::UserOf {
+            token_id: "1".to_string(),
+        };
+        let res: UserOfResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg.clone()).unwrap()).unwrap();
+        assert_eq!(Some(Addr::unchecked("scholar")), res.user);
+        assert_eq!(Some(expires), res.expires);
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(3600);
+        let res: UserOfResponse =
+            from_binary(&query(deps.as_ref(), env, query_msg.clone()).unwrap()).unwrap();
+        assert_eq!(None, res.user);
+
+        // the user is cleared on transfer
+        let exec_msg = ExecuteMsg::TransferNft {
+            recipient: PUBLIC.to_string(),
+            token_id: "1".to_string(),
+        };
+        execute(deps.as_mut(), mock_env(), owner, exec_msg).unwrap();
+        let res: UserOfResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(None, res.user);
+        assert_eq!(None, res.expires);
+    }
+
+    #[test]
+    fn rental() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info.clone(), exec_msg).unwrap();
+
+        let rent_msg = ExecuteMsg::Rent {
+            token_id: "1".to_string(),
+            days: 2,
+        };
+        let renter = mock_info(PUBLIC, &coins(20, "uusd"));
+        let res = execute(deps.as_mut(), mock_env(), renter.clone(), rent_msg.clone());
+        assert_eq!(ContractError::NotListed {}, res.unwrap_err());
+
+        let exec_msg = ExecuteMsg::ListForRent {
+            token_id: "1".to_string(),
+            price_per_day: coin(10, "uusd"),
+            max_days: 7,
+        };
+        let res = execute(deps.as_mut(), mock_env(), info, exec_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let owner = mock_info(OWNER, &[]);
+        for max_days in [0, 366] {
+            let exec_msg = ExecuteMsg::ListForRent {
+                token_id: "1".to_string(),
+                price_per_day: coin(10, "uusd"),
+                max_days,
+            };
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+            assert_eq!(
+                ContractError::InvalidRentalDays { max_days: 365 },
+                res.unwrap_err()
+            );
+        }
+        for price_per_day in [coin(0, "uusd"), coin(10, "")] {
+            let exec_msg = ExecuteMsg::ListForRent {
+                token_id: "1".to_string(),
+                price_per_day,
+                max_days: 7,
+            };
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+            assert_eq!(ContractError::InvalidRentalPrice {}, res.unwrap_err());
+        }
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::RentalListings {
+            start_after: None,
+            limit: None,
+        };
+        let res: RentalListingsResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!("1", res.listings[0].token_id);
+        assert_eq!(Addr::unchecked(OWNER), res.listings[0].listing.owner);
+
+        let exec_msg = ExecuteMsg::Rent {
+            token_id: "1".to_string(),
+            days: 8,
+        };
+        let res = execute(deps.as_mut(), mock_env(), renter.clone(), exec_msg);
+        assert_eq!(
+            ContractError::InvalidRentalDays { max_days: 7 },
+            res.unwrap_err()
+        );
+        // the exact payment is required
+        for funds in [
+            coins(10, "uusd"),
+            coins(21, "uusd"),
+            vec![coin(20, "uusd"), coin(1, "uluna")],
+        ] {
+            let res = execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(PUBLIC, &funds),
+                rent_msg.clone(),
+            );
+            assert_eq!(ContractError::Funds {}, res.unwrap_err());
+        }
+        execute(deps.as_mut(), mock_env(), renter.clone(), rent_msg.clone()).unwrap();
+
+        // the renter is the user and the token is locked until the rental ends
+        let expires = Expiration::AtTime(mock_env().block.time.plus_seconds(2 * 86400));
+        let query_msg = QueryMsg::UserOf {
+            token_id: "1".to_string(),
+        };
+        let res: UserOfResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(Some(Addr::unchecked(PUBLIC)), res.user);
+        assert_eq!(Some(expires), res.expires);
+
+        let transfer_msg = ExecuteMsg::TransferNft {
+            recipient: CREATOR.to_string(),
+            token_id: "1".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            owner.clone(),
+            transfer_msg.clone(),
+        );
+        assert_eq!(ContractError::TokenRented { expires }, res.unwrap_err());
+        let res = execute(deps.as_mut(), mock_env(), renter.clone(), rent_msg);
+        assert_eq!(ContractError::TokenRented { expires }, res.unwrap_err());
+        let claim_msg = ExecuteMsg::ClaimRentalPayout {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), claim_msg.clone());
+        assert_eq!(ContractError::TokenRented { expires }, res.unwrap_err());
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(2 * 86400);
+        let res = execute(deps.as_mut(), env.clone(), renter, claim_msg.clone());
+        assert_eq!(ContractError::Unauthorized {}, res.unwrap_err());
+        let res = execute(deps.as_mut(), env.clone(), owner.clone(), claim_msg.clone()).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: OWNER.to_string(),
+                amount: coins(20, "uusd"),
+            }),
+            res.messages[0].msg
+        );
+        let res = execute(deps.as_mut(), env.clone(), owner.clone(), claim_msg);
+        assert_eq!(ContractError::RentalNotFound {}, res.unwrap_err());
+
+        // the listing does not survive a transfer
+        execute(deps.as_mut(), env, owner, transfer_msg).unwrap();
+        let query_msg = QueryMsg::Rental {
+            token_id: "1".to_string(),
+        };
+        let res: RentalResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(None, res.listing);
+        assert_eq!(None, res.rental);
+    }
+
+    #[test]
+    fn fractional_vault() {
+        let mut deps = mock_dependencies(&[]);
+
+        let info = mock_info(CREATOR, &[]);
+        let init_msg = InstantiateMsg {
+            name: "SpaceShips".to_string(),
+            symbol: "SPACE".to_string(),
+            minter: CREATOR.to_string(),
+            token_supply: None,
+            buy_amount: 0,
+            available: false,
+            collection_info: None,
+        };
+
+        instantiate(deps.as_mut(), mock_env(), info.clone(), init_msg).unwrap();
+
+        let exec_msg = ExecuteMsg::Mint(MintMsg {
+            token_id: "1".to_string(),
+            owner: OWNER.to_string(),
+            token_uri: None,
+            extension: None,
+        });
+        execute(deps.as_mut(), mock_env(), info, exec_msg).unwrap();
+
+        let owner = mock_info(OWNER, &[]);
+        for reserve_price in [coin(0, "uusd"), coin(1000, "")] {
+            let exec_msg = ExecuteMsg::Fractionalize {
+                token_id: "1".to_string(),
+                shares: Uint128::new(100),
+                reserve_price,
+                auction_duration: 3600,
+            };
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+            assert_eq!(ContractError::InvalidReservePrice {}, res.unwrap_err());
+        }
+        for auction_duration in [0, 30 * 86400 + 1, u64::MAX] {
+            let exec_msg = ExecuteMsg::Fractionalize {
+                token_id: "1".to_string(),
+                shares: Uint128::new(100),
+                reserve_price: coin(1000, "uusd"),
+                auction_duration,
+            };
+            let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+            assert_eq!(
+                ContractError::InvalidAuctionDuration { max: 30 * 86400 },
+                res.unwrap_err()
+            );
+        }
+        let exec_msg = ExecuteMsg::Fractionalize {
+            token_id: "1".to_string(),
+            shares: Uint128::new(100),
+            reserve_price: coin(1000, "uusd"),
+            auction_duration: 3600,
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+
+        // the token is locked in the contract
+        let contract = RestNFTContract::default();
+        let res = contract
+            .owner_of(deps.as_ref(), mock_env(), "1".to_string(), false)
+            .unwrap();
+        assert_eq!(MOCK_CONTRACT_ADDR, res.owner);
+
+        let exec_msg = ExecuteMsg::TransferShares {
+            token_id: "1".to_string(),
+            recipient: PUBLIC.to_string(),
+            amount: Uint128::new(101),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg);
+        assert_eq!(ContractError::InsufficientBalance {}, res.unwrap_err());
+        let exec_msg = ExecuteMsg::TransferShares {
+            token_id: "1".to_string(),
+            recipient: PUBLIC.to_string(),
+            amount: Uint128::new(30),
+        };
+        execute(deps.as_mut(), mock_env(), owner.clone(), exec_msg).unwrap();
+
+        let query_msg = QueryMsg::ShareHolders {
+            token_id: "1".to_string(),
+            start_after: None,
+            limit: None,
+        };
+        let res: ShareHoldersResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(
+            vec![
+                ShareBalance {
+                    holder: OWNER.to_string(),
+                    balance: Uint128::new(70),
+                },
+                ShareBalance {
+                    holder: PUBLIC.to_string(),
+                    balance: Uint128::new(30),
+                },
+            ],
+            res.holders
+        );
+
+        // buyout auction at the reserve price
+        let bid_msg = ExecuteMsg::BidBuyout {
+            token_id: "1".to_string(),
+        };
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(999, "uusd")),
+            bid_msg.clone(),
+        );
+        assert_eq!(
+            ContractError::BidTooLow {
+                minimum: "1000uusd".to_string()
+            },
+            res.unwrap_err()
+        );
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("alice", &coins(1000, "uusd")),
+            bid_msg.clone(),
+        )
+        .unwrap();
+        let res = execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &coins(1001, "uusd")),
+            bid_msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: coins(1000, "uusd"),
+            }),
+            res.messages[0].msg
+        );
+
+        let settle_msg = ExecuteMsg::SettleBuyout {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), settle_msg.clone());
+        assert_eq!(ContractError::AuctionNotEnded {}, res.unwrap_err());
+        let redeem_msg = ExecuteMsg::Redeem {
+            token_id: "1".to_string(),
+        };
+        let res = execute(deps.as_mut(), mock_env(), owner.clone(), redeem_msg.clone());
+        assert_eq!(ContractError::NotBoughtOut {}, res.unwrap_err());
+
+        let mut env = mock_env();
+        env.block.time = env.block.time.plus_seconds(3600);
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("carol", &coins(2000, "uusd")),
+            bid_msg,
+        );
+        assert_eq!(ContractError::AuctionEnded {}, res.unwrap_err());
+        execute(deps.as_mut(), env.clone(), owner.clone(), settle_msg).unwrap();
+        let res = contract
+            .owner_of(deps.as_ref(), mock_env(), "1".to_string(), false)
+            .unwrap();
+        assert_eq!("bob", res.owner);
+
+        // the buyer cannot fractionalize again before all shares are redeemed
+        let refractionalize_msg = ExecuteMsg::Fractionalize {
+            token_id: "1".to_string(),
+            shares: Uint128::new(10),
+            reserve_price: coin(1000, "uusd"),
+            auction_duration: 3600,
+        };
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            mock_info("bob", &[]),
+            refractionalize_msg.clone(),
+        );
+        assert_eq!(ContractError::Fractionalized {}, res.unwrap_err());
+
+        // holders redeem their part of the proceeds
+        let res = execute(
+            deps.as_mut(),
+            env.clone(),
+            owner.clone(),
+            redeem_msg.clone(),
+        )
+        .unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: OWNER.to_string(),
+                amount: coins(700, "uusd"),
+            }),
+            res.messages[0].msg
+        );
+        let res = execute(deps.as_mut(), env.clone(), owner, redeem_msg.clone());
+        assert_eq!(ContractError::InsufficientBalance {}, res.unwrap_err());
+
+        let query_msg = QueryMsg::Shares {
+            token_id: "1".to_string(),
+            holder: PUBLIC.to_string(),
+        };
+        let res: SharesResponse =
+            from_binary(&query(deps.as_ref(), mock_env(), query_msg).unwrap()).unwrap();
+        assert_eq!(Uint128::new(30), res.balance);
+        assert_eq!(Uint128::new(30), res.total_shares);
+
+        let res = execute(deps.as_mut(), env, mock_info(PUBLIC, &[]), redeem_msg).unwrap();
+        assert_eq!(
+            CosmosMsg::Bank(BankMsg::Send {
+                to_address: PUBLIC.to_string(),
+                amount: coins(301, "uusd"),
+            }),
+            res.messages[0].msg
+        );
+        let query_msg = QueryMsg::Vault {
+            token_id: "1".to_string(),
+        };
+        assert!(query(deps.as_ref(), mock_env(), query_msg).is_err());
+
+        // fully redeemed, the token can be fractionalized again
+        execute(
+            deps.as_mut(),
+            mock_env(),
+            mock_info("bob", &[]),
+            refractionalize_msg,
+        )
+        .unwrap();
+    }
 }
@@ -1,8 +1,10 @@
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-use cosmwasm_std::Binary;
-use cw721::Expiration;
+use cosmwasm_std::{Addr, Binary, Coin, Timestamp, Uint128};
+use cw0::Duration;
+use cw20::Cw20ReceiveMsg;
+use cw721::{AllNftInfoResponse, Approval, Cw721ReceiveMsg, Expiration, NftInfoResponse};
 use cw721_base::{
     msg::{
         ExecuteMsg as CW721ExecuteMsg, InstantiateMsg as CW721InstantiateMsg,
@@ -11,7 +13,10 @@ use cw721_base::{
     MintMsg as CW721MintMsg,
 };
 
-use crate::state::Extension;
+use crate::state::{
+    Child, CollectionInfo, Evolution, ExchangeRecord, ExchangeSource, Extension, Lineage,
+    MetadataChange, MetadataPatch, Operation, Recipe, Rental, RentalListing, SaleRecord,
+};
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct InstantiateMsg {
@@ -30,6 +35,9 @@ pub struct InstantiateMsg {
 
     pub buy_amount: u64,
     pub available: bool,
+
+    // collection-level metadata
+    pub collection_info: Option<CollectionInfo>,
 }
 
 impl From<InstantiateMsg> for CW721InstantiateMsg {
@@ -55,6 +63,14 @@ pub enum ExecuteMsg {
         extension: Extension,
     },
 
+    // Update only the given metadata fields, rejected when expected_version
+    // does not match the token's current metadata version
+    PatchMetadata {
+        token_id: String,
+        patch: MetadataPatch,
+        expected_version: Option<u64>,
+    },
+
     // Freeze token metadata
     Freeze {},
 
@@ -73,6 +89,31 @@ pub enum ExecuteMsg {
         level: String,
     },
 
+    // Configure experience based leveling, level_thresholds are the total
+    // experience needed for each level starting at level 1
+    SetProgression {
+        game_server: String,
+        level_thresholds: Vec<u64>,
+        max_level: u32,
+    },
+
+    // Grant experience to a token, can only be called by the game server
+    AddExperience {
+        token_id: String,
+        amount: u64,
+    },
+
+    // Recompute rarity scores of a page of tokens, only once frozen
+    RecomputeRarity {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    // Reindex a page of the tokens minted before 0.10.0, after migrating
+    Reindex {
+        limit: Option<u32>,
+    },
+
     SetBuyAmount {
         buy_amount: u64,
     },
@@ -81,10 +122,234 @@ pub enum ExecuteMsg {
         available: bool,
     },
 
+    // Set collection metadata, name and symbol are only changed when given
+    SetCollectionInfo {
+        name: Option<String>,
+        symbol: Option<String>,
+        collection_info: CollectionInfo,
+    },
+
+    // Set the prefix (and optional suffix) used to derive token_uri for
+    // tokens minted without one, unset with None
+    SetBaseUri {
+        base_uri: Option<String>,
+        suffix: Option<String>,
+    },
+
+    // Set how many metadata history entries are kept per token, None keeps all
+    SetHistoryRetention {
+        retention: Option<u64>,
+    },
+
     Buy {
         recipient: String,
     },
 
+    // Set the guardian allowed to pause the contract, None removes it.
+    // Only the minter can call this
+    SetGuardian {
+        guardian: Option<String>,
+    },
+
+    // Block the paused operations, guardian only
+    Pause {},
+
+    // Lift the pause, guardian only
+    Unpause {},
+
+    // Set whether a token, or the collection when token_id is None, can be
+    // transferred and approved. Only the minter can call this
+    SetTransferable {
+        token_id: Option<String>,
+        transferable: bool,
+    },
+
+    // Set how long tokens bought through Buy cannot be transferred or sent,
+    // None disables the lock for future sales. Only the minter can call this
+    SetTransferLock {
+        duration: Option<Duration>,
+    },
+
+    // Lock a token for rewards, it cannot be transferred or burned until
+    // unstaked. Only the token owner can call this
+    Stake {
+        token_id: String,
+    },
+
+    Unstake {
+        token_id: String,
+    },
+
+    // Burn the input tokens and mint the recipe result to the caller, who
+    // must be able to send every input
+    Fuse {
+        token_ids: Vec<String>,
+        recipe_id: String,
+    },
+
+    // Create or replace a fusion recipe. Only the minter can call this
+    SetRecipe {
+        recipe_id: String,
+        recipe: Recipe,
+    },
+
+    RemoveRecipe {
+        recipe_id: String,
+    },
+
+    // Replace the token metadata with the next stage of the evolution table,
+    // keyed by the token name. Allowed after freeze. Owner or approved only
+    Evolve {
+        token_id: String,
+    },
+
+    // Create or replace the evolution from a stage. Minter only, not after freeze
+    SetEvolution {
+        stage: String,
+        evolution: Evolution,
+    },
+
+    RemoveEvolution {
+        stage: String,
+    },
+
+    // Delegate the usage of a token until it expires, without transferring it.
+    // None clears the user, which is also cleared on every transfer.
+    // Owner or approved only
+    SetUser {
+        token_id: String,
+        user: Option<String>,
+        expires: Expiration,
+    },
+
+    // List a token for rent at a price per day. Owner or approved only
+    ListForRent {
+        token_id: String,
+        price_per_day: Coin,
+        max_days: u32,
+    },
+
+    // Withdraw a rental listing, active rentals are kept
+    Unlist {
+        token_id: String,
+    },
+
+    // Rent a listed token for some days, paying the price into escrow. The
+    // renter becomes the token user and the token cannot be transferred
+    // until the rental ends
+    Rent {
+        token_id: String,
+        days: u32,
+    },
+
+    // Pay the escrowed payment of an ended rental to the owner
+    ClaimRentalPayout {
+        token_id: String,
+    },
+
+    // Lock a token in the contract against shares credited to its owner.
+    // Owner or approved only
+    Fractionalize {
+        token_id: String,
+        shares: Uint128,
+        reserve_price: Coin,
+        auction_duration: u64,
+    },
+
+    TransferShares {
+        token_id: String,
+        recipient: String,
+        amount: Uint128,
+    },
+
+    // Bid at least the reserve price to buy out a fractionalized token. The
+    // first bid starts the auction, outbid bids are refunded
+    BidBuyout {
+        token_id: String,
+    },
+
+    // Send the token to the winning bidder once the auction ended
+    SettleBuyout {
+        token_id: String,
+    },
+
+    // Exchange all the caller shares for their part of the buyout proceeds
+    Redeem {
+        token_id: String,
+    },
+
+    // Equip a token of this contract to a parent token. The contract holds the
+    // child until it is unequipped, so it moves along with its parent.
+    // The caller must be able to send both tokens
+    Equip {
+        token_id: String,
+        child_token_id: String,
+    },
+
+    // Return an equipped child, or part of a cw20 balance, to the parent owner.
+    // The caller must be able to send the parent
+    Unequip {
+        token_id: String,
+        child: Child,
+    },
+
+    // Drop an external child, or part of a cw20 balance, from the parent
+    // without sending it back, for children whose contract refuses the return.
+    // The caller must be able to send the parent
+    Detach {
+        token_id: String,
+        child: Child,
+    },
+
+    // Equip an external cw721 token, with a ReceiveMsg
+    ReceiveNft(Cw721ReceiveMsg),
+
+    // Equip a cw20 balance, with a ReceiveMsg
+    Receive(Cw20ReceiveMsg),
+
+    // Allow tokens or cw20 balances of a contract to be equipped. Minter only
+    SetChildContract {
+        contract: String,
+    },
+
+    RemoveChildContract {
+        contract: String,
+    },
+
+    // Whitelist a collection whose tokens can be exchanged. Minter only
+    SetExchangeSource {
+        contract: String,
+        source: ExchangeSource,
+    },
+
+    RemoveExchangeSource {
+        contract: String,
+    },
+
+    // Mint a child to the caller with traits drawn from both parents, which
+    // the caller must be able to send. Parents cannot breed again until
+    // their cooldown ends
+    Breed {
+        parent_a: String,
+        parent_b: String,
+    },
+
+    // Set the parent cooldown, None disables breeding. Minter only
+    SetBreeding {
+        cooldown: Option<Duration>,
+    },
+
+    // Move a non-transferable token, e.g. out of a lost wallet. Minter only
+    RecoverToken {
+        token_id: String,
+        recipient: String,
+    },
+
+    // Set the operations blocked while paused, guardian only
+    SetPausedOperations {
+        operations: Vec<Operation>,
+    },
+
     /// Mint a new NFT, can only be called by the contract minter
     Mint(MintMsg),
 
@@ -173,9 +438,163 @@ pub enum QueryMsg {
 
     Sales {},
 
+    /// Returns the sales ledger, oldest first
+    SalesHistory {
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
+    /// Returns aggregate sales statistics
+    SalesStats {},
+
+    /// Returns whether the contract is paused and what is blocked
+    PauseStatus {},
+
+    /// Returns the parents and generation of a token
+    Lineage {
+        token_id: String,
+    },
+
+    /// Returns the evolution from a stage
+    Evolution {
+        stage: String,
+    },
+
+    /// Lists the evolution table
+    Evolutions {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the vault of a fractionalized token
+    Vault {
+        token_id: String,
+    },
+
+    /// Returns the shares of a holder in a fractionalized token
+    Shares {
+        token_id: String,
+        holder: String,
+    },
+
+    /// Lists the share holders of a fractionalized token
+    ShareHolders {
+        token_id: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the rental listing and the current or unclaimed rental of a token
+    Rental {
+        token_id: String,
+    },
+
+    /// Lists the tokens listed for rent
+    RentalListings {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the delegated user of a token, None once expired
+    UserOf {
+        token_id: String,
+    },
+
+    /// Returns the token issued for a source token, if exchanged
+    Exchanged {
+        source_contract: String,
+        source_token_id: String,
+    },
+
+    /// Lists the collections whose tokens can be exchanged
+    ExchangeSources {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the children equipped to a token and the parent it is equipped to
+    Equipped {
+        token_id: String,
+    },
+
+    /// Returns a fusion recipe
+    Recipe {
+        recipe_id: String,
+    },
+
+    /// Lists the fusion recipes
+    Recipes {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the stake of a token and its total staked duration
+    StakeInfo {
+        token_id: String,
+    },
+
+    /// Lists the tokens staked by the owner
+    StakedTokens {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns when the post-purchase transfer lock of the token lifts
+    TransferLock {
+        token_id: String,
+    },
+
+    /// Returns whether the token can be transferred, or the collection default
+    /// when token_id is None
+    Transferable {
+        token_id: Option<String>,
+    },
+
     // Checks if contract is frozen/finalised
     Frozen {},
 
+    /// Returns the collection-level metadata
+    CollectionInfo {},
+
+    /// Returns the experience and computed level of a token
+    Experience {
+        token_id: String,
+    },
+
+    /// Returns the tokens having the given attribute
+    TokensByTrait {
+        trait_type: String,
+        value: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns how many tokens have each value of the given trait_type
+    TraitCounts {
+        trait_type: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the rarity score of a token
+    Rarity {
+        token_id: String,
+    },
+
+    /// Returns tokens ordered from rarest to most common
+    RarityRanking {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+
+    /// Returns the metadata change log of a token, oldest first
+    MetadataHistory {
+        token_id: String,
+        start_after: Option<u64>,
+        limit: Option<u32>,
+    },
+
     // Standard cw721 queries
     OwnerOf {
         token_id: String,
@@ -201,9 +620,29 @@ pub enum QueryMsg {
         start_after: Option<String>,
         limit: Option<u32>,
     },
+    /// Returns the tokens of an owner with their metadata and approvals
+    TokensWithInfo {
+        owner: String,
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Lists token ids between the exclusive start_after and end_before
+    /// bounds, highest first when reverse is set
     AllTokens {
         start_after: Option<String>,
+        end_before: Option<String>,
         limit: Option<u32>,
+        reverse: Option<bool>,
+    },
+    /// Returns the metadata of a page of tokens ordered by token id
+    NftInfoRange {
+        start_after: Option<String>,
+        limit: Option<u32>,
+    },
+    /// Returns AllNftInfo for each of the given tokens
+    AllNftInfoBatch {
+        token_ids: Vec<String>,
+        include_expired: Option<bool>,
     },
 }
 
@@ -247,17 +686,234 @@ impl From<QueryMsg> for CW721QueryMsg {
                 start_after,
                 limit,
             },
-            QueryMsg::AllTokens { start_after, limit } => {
-                CW721QueryMsg::AllTokens { start_after, limit }
-            }
             _ => panic!("cannot covert {:?} to CW721QueryMsg", msg),
         }
     }
 }
 
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SalesHistoryResponse {
+    pub sales: Vec<SaleRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SalesStatsResponse {
+    pub sold: u64,
+    /// Tokens that can still be minted under the token supply, None when supply is unlimited
+    pub remaining: Option<u64>,
+    pub unique_buyers: u64,
+    /// Total revenue per denom
+    pub revenue: Vec<Coin>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExperienceResponse {
+    pub experience: u64,
+    /// None when no progression is configured
+    pub level: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TraitCount {
+    pub trait_type: String,
+    pub value: String,
+    pub count: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TraitCountsResponse {
+    pub counts: Vec<TraitCount>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RarityResponse {
+    pub token_id: String,
+    /// Sum over the token traits of num_tokens * 1000 / tokens_with_trait
+    pub score: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RarityRankingResponse {
+    pub tokens: Vec<RarityResponse>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TokenNftInfo {
+    pub token_id: String,
+    pub info: NftInfoResponse<Extension>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct NftInfoRangeResponse {
+    pub tokens: Vec<TokenNftInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TokenWithInfo {
+    pub token_id: String,
+    pub token_uri: Option<String>,
+    pub extension: Extension,
+    /// Unexpired approvals of the token
+    pub approvals: Vec<Approval>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TokensWithInfoResponse {
+    pub tokens: Vec<TokenWithInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TokenAllNftInfo {
+    pub token_id: String,
+    pub info: AllNftInfoResponse<Extension>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct AllNftInfoBatchResponse {
+    pub tokens: Vec<TokenAllNftInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct LineageResponse {
+    pub token_id: String,
+    pub lineage: Lineage,
+    /// End of the breeding cooldown, None when the token can breed
+    pub cooldown: Option<Expiration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RecipeInfo {
+    pub recipe_id: String,
+    pub recipe: Recipe,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RecipesResponse {
+    pub recipes: Vec<RecipeInfo>,
+}
+
+/// Message attached to ReceiveNft and Receive
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ReceiveMsg {
+    /// Equip the received asset to a token the sender can send
+    Equip { token_id: String },
+    /// Exchange a token of a whitelisted collection for the mapped token,
+    /// issued from the minter inventory or minted
+    Exchange {},
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SharesResponse {
+    pub balance: Uint128,
+    pub total_shares: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ShareBalance {
+    pub holder: String,
+    pub balance: Uint128,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ShareHoldersResponse {
+    pub holders: Vec<ShareBalance>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RentalResponse {
+    pub listing: Option<RentalListing>,
+    pub rental: Option<Rental>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RentalListingInfo {
+    pub token_id: String,
+    pub listing: RentalListing,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RentalListingsResponse {
+    pub listings: Vec<RentalListingInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct UserOfResponse {
+    pub user: Option<Addr>,
+    pub expires: Option<Expiration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExchangedResponse {
+    pub exchange: Option<ExchangeRecord>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExchangeSourceInfo {
+    pub contract: String,
+    pub source: ExchangeSource,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExchangeSourcesResponse {
+    pub sources: Vec<ExchangeSourceInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct EquippedResponse {
+    /// Token the queried token is equipped to
+    pub parent: Option<String>,
+    pub children: Vec<Child>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct EvolutionInfo {
+    pub stage: String,
+    pub evolution: Evolution,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct EvolutionsResponse {
+    pub evolutions: Vec<EvolutionInfo>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct StakeInfoResponse {
+    pub staked: bool,
+    /// Owner and start of the current stake, None when not staked
+    pub owner: Option<Addr>,
+    pub staked_since: Option<Timestamp>,
+    /// Seconds staked over all stakes, including the current one
+    pub total_staked_seconds: u64,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TransferLockResponse {
+    /// None when the token is not locked
+    pub expires: Option<Expiration>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TransferableResponse {
+    pub transferable: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct PauseStatusResponse {
+    pub paused: bool,
+    pub guardian: Option<Addr>,
+    /// Operations blocked while paused
+    pub paused_operations: Vec<Operation>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MetadataHistoryResponse {
+    pub changes: Vec<MetadataChange>,
+}
+
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
 #[serde(rename_all = "snake_case")]
 pub struct MigrateMsg<T> {
-    pub version: String,
+    /// Replaces the stored config, except for the frozen flag
     pub config: Option<T>,
 }
@@ -1,4 +1,5 @@
-use cosmwasm_std::Empty;
+use cosmwasm_std::{Addr, Coin, Empty, Timestamp, Uint128};
+use cw0::Expiration;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
@@ -25,3 +26,224 @@ pub struct Metadata {
     pub animation_url: Option<String>,
     pub youtube_url: Option<String>,
 }
+
+/// Change to a single optional field, leaving the field out of a patch keeps it unchanged
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldPatch<T> {
+    Set(T),
+    Clear,
+}
+
+impl<T> FieldPatch<T> {
+    pub fn apply(self, field: &mut Option<T>) {
+        *field = match self {
+            FieldPatch::Set(value) => Some(value),
+            FieldPatch::Clear => None,
+        }
+    }
+}
+
+/// Upserts (Set) or removes (Clear) the attributes matching trait_type
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct TraitPatch {
+    pub trait_type: String,
+    pub value: FieldPatch<String>,
+    /// Replaces display_type when given, ignored on Clear
+    pub display_type: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct MetadataPatch {
+    pub token_uri: Option<FieldPatch<String>>,
+    pub image: Option<FieldPatch<String>>,
+    pub image_data: Option<FieldPatch<String>>,
+    pub external_url: Option<FieldPatch<String>>,
+    pub description: Option<FieldPatch<String>>,
+    pub name: Option<FieldPatch<String>>,
+    pub attributes: Option<Vec<TraitPatch>>,
+    pub background_color: Option<FieldPatch<String>>,
+    pub animation_url: Option<FieldPatch<String>>,
+    pub youtube_url: Option<FieldPatch<String>>,
+}
+
+// see: https://docs.opensea.io/docs/contract-level-metadata
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct CollectionInfo {
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub external_link: Option<String>,
+    pub banner_image: Option<String>,
+    /// URI of the off-chain contract-level metadata JSON
+    pub contract_uri: Option<String>,
+}
+
+/// A single entry of a token's metadata change log
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct MetadataChange {
+    /// Metadata version produced by this change, starting at 1
+    pub version: u64,
+    pub height: u64,
+    pub editor: Addr,
+    pub old_token_uri: Option<String>,
+    pub new_token_uri: Option<String>,
+    pub old_extension: Extension,
+    pub new_extension: Extension,
+}
+
+/// A primary sale made through Buy
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct SaleRecord {
+    /// Sequence number of the sale, starting at 1
+    pub id: u64,
+    pub token_id: String,
+    pub buyer: Addr,
+    pub recipient: Addr,
+    pub price: Coin,
+    pub time: Timestamp,
+}
+
+/// Classes of operations that can be blocked while the contract is paused
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Operation {
+    /// TransferNft and SendNft
+    Transfer,
+    /// Approve, Revoke, ApproveAll and RevokeAll
+    Approve,
+    Burn,
+    Buy,
+    Mint,
+    /// Update, PatchMetadata, SetLevel and AddExperience
+    Update,
+    /// Stake and Unstake
+    Stake,
+}
+
+impl Operation {
+    pub const ALL: [Operation; 7] = [
+        Operation::Transfer,
+        Operation::Approve,
+        Operation::Burn,
+        Operation::Buy,
+        Operation::Mint,
+        Operation::Update,
+        Operation::Stake,
+    ];
+}
+
+/// How the level of a fused token is derived from the levels of its inputs
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum LevelInheritance {
+    Max,
+    Sum,
+    Average,
+}
+
+/// Result of fusing `input_count` tokens
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Recipe {
+    pub input_count: u32,
+    pub token_uri: Option<String>,
+    pub metadata: Metadata,
+    /// Sets the level trait of the result from the input levels, None keeps
+    /// the level of the recipe metadata
+    pub level_inheritance: Option<LevelInheritance>,
+}
+
+/// Parents and generation of a bred token, original tokens are generation 0
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
+pub struct Lineage {
+    pub parents: Vec<String>,
+    pub generation: u32,
+}
+
+/// Next stage of tokens named after the stage they evolve from. Every
+/// condition set must be met to evolve
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Evolution {
+    /// Replaces the token metadata, the level trait is kept when missing
+    pub metadata: Metadata,
+    /// Replaces the token_uri when set
+    pub token_uri: Option<String>,
+    /// Minimum value of the level trait
+    pub min_level: Option<u32>,
+    /// Minimum time the token must have been held by its owner
+    pub min_hold_seconds: Option<u64>,
+    /// Paid to the minter on evolution
+    pub fee: Option<Coin>,
+}
+
+/// Asset equipped to a parent token
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Child {
+    /// Token of this contract
+    Token { token_id: String },
+    /// Token of an external cw721 contract
+    Cw721 { contract: String, token_id: String },
+    /// Balance of a cw20 contract
+    Cw20 { contract: String, amount: Uint128 },
+}
+
+/// Collection whose tokens can be exchanged for tokens of this contract
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExchangeSource {
+    /// Prepended to the source token_id to get the issued token_id
+    pub token_id_prefix: Option<String>,
+    /// Received tokens are forwarded there, e.g. a burn address. None holds them
+    pub forward_to: Option<String>,
+}
+
+/// A source token exchanged through ReceiveNft
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct ExchangeRecord {
+    /// Token issued for the source token
+    pub token_id: String,
+    pub recipient: Addr,
+    pub time: Timestamp,
+}
+
+/// Offer to rent out the usage of a token
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct RentalListing {
+    /// Owner when listed, paid once rentals end
+    pub owner: Addr,
+    pub price_per_day: Coin,
+    pub max_days: u32,
+}
+
+/// Rental of a token, its payment is escrowed until claimed by the owner
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Rental {
+    pub renter: Addr,
+    pub owner: Addr,
+    pub payment: Coin,
+    pub expires: Expiration,
+}
+
+/// Bid of a buyout auction
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct BuyoutBid {
+    pub bidder: Addr,
+    pub amount: Coin,
+}
+
+/// Token locked in the contract against fungible shares
+#[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug)]
+pub struct Vault {
+    /// Owner who fractionalized the token
+    pub owner: Addr,
+    /// Outstanding shares, decreasing as they are redeemed after a buyout
+    pub total_shares: Uint128,
+    /// Minimum buyout bid
+    pub reserve_price: Coin,
+    /// Length of the buyout auction from the first bid, in seconds
+    pub auction_duration: u64,
+    pub highest_bid: Option<BuyoutBid>,
+    /// Set by the first bid
+    pub auction_end: Option<Timestamp>,
+    /// Buyout proceeds left to redeem, set once the buyout is settled
+    pub proceeds: Option<Coin>,
+}